@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS Sessions(
+    id text NOT NULL PRIMARY KEY,
+    username text NOT NULL REFERENCES Users(username) ON DELETE CASCADE ON UPDATE CASCADE,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at DATETIME NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS sessions_username ON Sessions(username);
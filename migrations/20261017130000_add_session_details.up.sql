@@ -0,0 +1,3 @@
+ALTER TABLE Sessions ADD COLUMN last_seen DATETIME NULL;
+ALTER TABLE Sessions ADD COLUMN user_agent TEXT NULL;
+ALTER TABLE Sessions ADD COLUMN ip TEXT NULL;
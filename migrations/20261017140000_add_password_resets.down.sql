@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS password_reset_requests_ip;
+DROP INDEX IF EXISTS password_reset_requests_email;
+DROP TABLE IF EXISTS PasswordResetRequests;
+DROP INDEX IF EXISTS password_resets_username;
+DROP TABLE IF EXISTS PasswordResets;
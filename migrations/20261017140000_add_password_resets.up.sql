@@ -0,0 +1,19 @@
+CREATE TABLE IF NOT EXISTS PasswordResets(
+    token_hash text NOT NULL PRIMARY KEY,
+    username text NOT NULL REFERENCES Users(username) ON DELETE CASCADE ON UPDATE CASCADE,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at DATETIME NOT NULL,
+    consumed_at DATETIME NULL
+);
+
+CREATE INDEX IF NOT EXISTS password_resets_username ON PasswordResets(username);
+
+CREATE TABLE IF NOT EXISTS PasswordResetRequests(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    email text NOT NULL,
+    ip text NOT NULL,
+    requested_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS password_reset_requests_email ON PasswordResetRequests(email, requested_at);
+CREATE INDEX IF NOT EXISTS password_reset_requests_ip ON PasswordResetRequests(ip, requested_at);
@@ -0,0 +1 @@
+-- deleted orphans cannot be restored
@@ -0,0 +1,8 @@
+-- foreign keys were not enforced before, remove what the cascades should have removed
+DELETE FROM Articles WHERE author NOT IN (SELECT username FROM Users);
+DELETE FROM Follows WHERE follower NOT IN (SELECT username FROM Users) OR influencer NOT IN (SELECT username FROM Users);
+DELETE FROM ArticleTags WHERE article NOT IN (SELECT slug FROM Articles);
+DELETE FROM FavArticles WHERE article NOT IN (SELECT slug FROM Articles) OR username NOT IN (SELECT username FROM Users);
+DELETE FROM Comments WHERE article NOT IN (SELECT slug FROM Articles) OR username NOT IN (SELECT username FROM Users);
+DELETE FROM Sessions WHERE username NOT IN (SELECT username FROM Users);
+DELETE FROM PasswordResets WHERE username NOT IN (SELECT username FROM Users);
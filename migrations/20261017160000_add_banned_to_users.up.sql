@@ -0,0 +1 @@
+ALTER TABLE Users ADD COLUMN banned_at DATETIME NULL;
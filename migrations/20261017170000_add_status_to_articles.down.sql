@@ -0,0 +1,23 @@
+DROP TRIGGER IF EXISTS articles_ai;
+DROP TRIGGER IF EXISTS articles_au;
+DROP TRIGGER IF EXISTS articles_ad;
+
+CREATE TRIGGER articles_ai AFTER INSERT ON articles BEGIN
+    INSERT INTO articles_fts(rowid, slug, title, description, body) VALUES (NEW.oid, NEW.slug, NEW.title,NEW.description, NEW.body);
+END;
+
+CREATE TRIGGER articles_au AFTER UPDATE ON articles BEGIN
+    INSERT INTO articles_fts(articles_fts, rowid, slug, title, description, body) VALUES ('delete', OLD.oid, OLD.slug, OLD.title, OLD.description, OLD.body);
+    INSERT INTO articles_fts(rowid, slug, title, description, body) VALUES (NEW.oid, NEW.slug, NEW.title, NEW.description, NEW.body);
+END;
+
+CREATE TRIGGER articles_ad AFTER DELETE ON articles BEGIN
+    INSERT INTO articles_fts(articles_fts, rowid, slug, title, description, body) VALUES ('delete', OLD.oid, OLD.slug, OLD.title, OLD.description, OLD.body);
+END;
+
+DROP INDEX IF EXISTS articles_status_created_at;
+
+ALTER TABLE Articles DROP COLUMN status;
+
+-- Former drafts were left out of the index
+INSERT INTO Articles_fts(Articles_fts) VALUES('rebuild');
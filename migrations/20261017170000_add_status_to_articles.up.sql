@@ -0,0 +1,22 @@
+-- Articles are either a 'draft', only visible to their author, or 'published'
+ALTER TABLE Articles ADD COLUMN status TEXT NOT NULL DEFAULT 'published';
+
+CREATE INDEX IF NOT EXISTS articles_status_created_at ON Articles(status, created_at);
+
+-- Drafts stay out of the full-text index, a row only gets indexed while it is published
+DROP TRIGGER IF EXISTS articles_ai;
+DROP TRIGGER IF EXISTS articles_au;
+DROP TRIGGER IF EXISTS articles_ad;
+
+CREATE TRIGGER articles_ai AFTER INSERT ON articles WHEN NEW.status = 'published' BEGIN
+    INSERT INTO articles_fts(rowid, slug, title, description, body) VALUES (NEW.oid, NEW.slug, NEW.title, NEW.description, NEW.body);
+END;
+
+CREATE TRIGGER articles_au AFTER UPDATE ON articles BEGIN
+    INSERT INTO articles_fts(articles_fts, rowid, slug, title, description, body) SELECT 'delete', OLD.oid, OLD.slug, OLD.title, OLD.description, OLD.body WHERE OLD.status = 'published';
+    INSERT INTO articles_fts(rowid, slug, title, description, body) SELECT NEW.oid, NEW.slug, NEW.title, NEW.description, NEW.body WHERE NEW.status = 'published';
+END;
+
+CREATE TRIGGER articles_ad AFTER DELETE ON articles WHEN OLD.status = 'published' BEGIN
+    INSERT INTO articles_fts(articles_fts, rowid, slug, title, description, body) VALUES ('delete', OLD.oid, OLD.slug, OLD.title, OLD.description, OLD.body);
+END;
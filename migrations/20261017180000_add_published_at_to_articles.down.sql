@@ -0,0 +1,6 @@
+DROP INDEX IF EXISTS articles_status_published_at;
+
+-- Scheduled articles fall back to drafts
+UPDATE Articles SET status = 'draft' WHERE status = 'scheduled';
+
+ALTER TABLE Articles DROP COLUMN published_at;
@@ -0,0 +1,6 @@
+-- When an article went or goes live. NULL for drafts, in the future for 'scheduled' articles
+ALTER TABLE Articles ADD COLUMN published_at DATETIME NULL;
+
+UPDATE Articles SET published_at = created_at WHERE status = 'published';
+
+CREATE INDEX IF NOT EXISTS articles_status_published_at ON Articles(status, published_at);
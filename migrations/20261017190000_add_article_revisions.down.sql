@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS article_revisions_article;
+DROP TABLE IF EXISTS ArticleRevisions;
@@ -0,0 +1,25 @@
+-- Every save of an article, the latest one matches the article itself
+CREATE TABLE IF NOT EXISTS ArticleRevisions(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    article text NOT NULL REFERENCES Articles(slug) ON DELETE CASCADE ON UPDATE CASCADE,
+    author text NOT NULL REFERENCES Users(username) ON DELETE CASCADE ON UPDATE CASCADE,
+    title text NOT NULL,
+    description text NOT NULL,
+    body text NOT NULL,
+    tag_list text NOT NULL DEFAULT '',
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS article_revisions_article ON ArticleRevisions(article, id);
+
+-- Existing articles start their history with what they look like now
+INSERT INTO ArticleRevisions(article, author, title, description, body, tag_list, created_at)
+SELECT
+    a.slug,
+    a.author,
+    a.title,
+    a.description,
+    a.body,
+    coalesce((SELECT group_concat(tag, ' ') FROM (SELECT tag FROM ArticleTags WHERE article = a.slug ORDER BY tag)), ''),
+    a.updated_at
+FROM Articles as a;
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS article_slugs_article;
+DROP TABLE IF EXISTS ArticleSlugs;
@@ -0,0 +1,9 @@
+-- Slugs an article had before its title changed, they redirect to the current one.
+-- Renames cascade, so every old slug points straight at the current slug.
+CREATE TABLE IF NOT EXISTS ArticleSlugs(
+    old_slug text NOT NULL PRIMARY KEY,
+    article text NOT NULL REFERENCES Articles(slug) ON DELETE CASCADE ON UPDATE CASCADE,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS article_slugs_article ON ArticleSlugs(article);
@@ -0,0 +1 @@
+ALTER TABLE Articles DROP COLUMN version;
@@ -0,0 +1,2 @@
+-- Bumped on every save, the editor sends the version it loaded so stale saves get rejected
+ALTER TABLE Articles ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
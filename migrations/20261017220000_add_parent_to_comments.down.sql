@@ -0,0 +1,14 @@
+-- A column used by a foreign key can not be dropped, so the table is rebuilt flat.
+DROP INDEX IF EXISTS idx_comments_parent;
+DELETE FROM Comments WHERE deleted = 1;
+CREATE TABLE Comments_flat(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    article text NOT NULL REFERENCES Articles(slug) ON DELETE CASCADE ON UPDATE CASCADE,
+    username text NOT NULL REFERENCES Users(username) ON DELETE CASCADE ON UPDATE CASCADE,
+    body text NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+INSERT INTO Comments_flat(id, article, username, body, created_at)
+    SELECT id, article, username, body, created_at FROM Comments;
+DROP TABLE Comments;
+ALTER TABLE Comments_flat RENAME TO Comments;
@@ -0,0 +1,5 @@
+-- Replies point at the comment they answer. A deleted comment that still has replies stays
+-- as a placeholder with `deleted` set, so the thread under it keeps its place.
+ALTER TABLE Comments ADD COLUMN parent INTEGER NULL REFERENCES Comments(id) ON DELETE SET NULL;
+ALTER TABLE Comments ADD COLUMN deleted BOOLEAN NOT NULL DEFAULT 0;
+CREATE INDEX IF NOT EXISTS idx_comments_parent ON Comments(parent);
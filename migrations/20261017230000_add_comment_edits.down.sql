@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_comment_edits_comment;
+DROP TABLE IF EXISTS CommentEdits;
+ALTER TABLE Comments DROP COLUMN edited_at;
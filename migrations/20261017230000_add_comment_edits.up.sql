@@ -0,0 +1,10 @@
+-- Set when the author edits a comment, the bodies it had before are kept in CommentEdits
+ALTER TABLE Comments ADD COLUMN edited_at DATETIME NULL;
+CREATE TABLE IF NOT EXISTS CommentEdits(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    comment INTEGER NOT NULL REFERENCES Comments(id) ON DELETE CASCADE,
+    body text NOT NULL,
+    -- when this body was replaced
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX IF NOT EXISTS idx_comment_edits_comment ON CommentEdits(comment);
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_comment_mentions_username;
+DROP TABLE IF EXISTS CommentMentions;
@@ -0,0 +1,8 @@
+-- Users named with @username in a comment, so they can be notified
+CREATE TABLE IF NOT EXISTS CommentMentions(
+    comment INTEGER NOT NULL REFERENCES Comments(id) ON DELETE CASCADE,
+    username text NOT NULL REFERENCES Users(username) ON DELETE CASCADE ON UPDATE CASCADE,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (comment, username)
+);
+CREATE INDEX IF NOT EXISTS idx_comment_mentions_username ON CommentMentions(username, created_at);
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_notifications_username;
+DROP TABLE IF EXISTS Notifications;
@@ -0,0 +1,12 @@
+-- What happened to a user: `actor` followed them, favorited, commented on or replied under
+-- `article`, or mentioned them there. Unread until `read_at` is set.
+CREATE TABLE IF NOT EXISTS Notifications(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    username text NOT NULL REFERENCES Users(username) ON DELETE CASCADE ON UPDATE CASCADE,
+    kind text NOT NULL,
+    actor text NOT NULL REFERENCES Users(username) ON DELETE CASCADE ON UPDATE CASCADE,
+    article text NULL REFERENCES Articles(slug) ON DELETE CASCADE ON UPDATE CASCADE,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    read_at DATETIME NULL
+);
+CREATE INDEX IF NOT EXISTS idx_notifications_username ON Notifications(username, read_at, created_at);
@@ -0,0 +1,2 @@
+ALTER TABLE Users DROP COLUMN last_digest_at;
+ALTER TABLE Users DROP COLUMN email_digest;
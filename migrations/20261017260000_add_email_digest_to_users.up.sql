@@ -0,0 +1,4 @@
+-- How often a user gets their unread notifications by email: 'off', 'daily' or 'weekly'.
+-- The next digest covers what happened after `last_digest_at`.
+ALTER TABLE Users ADD COLUMN email_digest TEXT NOT NULL DEFAULT 'off';
+ALTER TABLE Users ADD COLUMN last_digest_at DATETIME NULL;
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_mail_queue_expires_at;
+DROP INDEX IF EXISTS idx_mail_queue_next_attempt;
+DROP TABLE IF EXISTS MailQueue;
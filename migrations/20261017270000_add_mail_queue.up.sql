@@ -0,0 +1,17 @@
+-- Mail waiting to be handed to the mail backend. Sent mail is removed, failed attempts are
+-- retried at `next_attempt_at` until it is cleared, which means the mail was given up on.
+-- Mail carrying a reset or confirmation link is dropped once the link expires at `expires_at`,
+-- sent or not, so the raw token does not outlive it in the database.
+CREATE TABLE IF NOT EXISTS MailQueue(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    recipient text NOT NULL,
+    subject text NOT NULL,
+    text_body text NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at DATETIME NULL DEFAULT CURRENT_TIMESTAMP,
+    last_error text NULL,
+    expires_at DATETIME NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX IF NOT EXISTS idx_mail_queue_next_attempt ON MailQueue(next_attempt_at);
+CREATE INDEX IF NOT EXISTS idx_mail_queue_expires_at ON MailQueue(expires_at);
@@ -0,0 +1,2 @@
+ALTER TABLE MailQueue DROP COLUMN html_body;
+ALTER TABLE Users DROP COLUMN locale;
@@ -0,0 +1,3 @@
+-- The language mail is written in, and the HTML part of queued mail next to the text one.
+ALTER TABLE Users ADD COLUMN locale TEXT NOT NULL DEFAULT 'en';
+ALTER TABLE MailQueue ADD COLUMN html_body TEXT NOT NULL DEFAULT '';
@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS idx_email_verifications_username;
+DROP TABLE IF EXISTS EmailVerifications;
+ALTER TABLE Users DROP COLUMN pending_email;
+ALTER TABLE Users DROP COLUMN email_verified;
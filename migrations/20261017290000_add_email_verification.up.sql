@@ -0,0 +1,18 @@
+-- Addresses have to be confirmed before mail goes to them. The ones from before are kept
+-- trusted, their owners could not have confirmed them. A new address waits in
+-- `pending_email` until it is confirmed and only then replaces `email`.
+ALTER TABLE Users ADD COLUMN email_verified BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE Users ADD COLUMN pending_email TEXT NULL;
+UPDATE Users SET email_verified=1;
+
+-- An emailed confirmation link for `email`, only the hash of its token is stored. Links are
+-- kept once used, with `consumed_at` set, since they also count the mail sent to each user.
+CREATE TABLE IF NOT EXISTS EmailVerifications(
+    token_hash text NOT NULL PRIMARY KEY,
+    username text NOT NULL REFERENCES Users(username) ON DELETE CASCADE ON UPDATE CASCADE,
+    email text NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at DATETIME NOT NULL,
+    consumed_at DATETIME NULL
+);
+CREATE INDEX IF NOT EXISTS idx_email_verifications_username ON EmailVerifications(username);
@@ -0,0 +1,8 @@
+DROP INDEX IF EXISTS idx_mfa_challenges_username;
+DROP TABLE IF EXISTS MfaChallenges;
+DROP TABLE IF EXISTS RecoveryCodes;
+ALTER TABLE Users DROP COLUMN totp_locked_until;
+ALTER TABLE Users DROP COLUMN totp_failures;
+ALTER TABLE Users DROP COLUMN totp_last_step;
+ALTER TABLE Users DROP COLUMN totp_pending_secret;
+ALTER TABLE Users DROP COLUMN totp_secret;
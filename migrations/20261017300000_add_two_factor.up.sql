@@ -0,0 +1,30 @@
+-- Optional TOTP second factor. `totp_pending_secret` is the secret being enrolled until a
+-- first code confirms it, `totp_last_step` is the time step of the last accepted code so it
+-- cannot be used twice.
+ALTER TABLE Users ADD COLUMN totp_secret TEXT NULL;
+ALTER TABLE Users ADD COLUMN totp_pending_secret TEXT NULL;
+ALTER TABLE Users ADD COLUMN totp_last_step INTEGER NULL;
+
+-- Wrong second factor codes in a row, across logins and settings. Reaching the limit locks
+-- the second factor until `totp_locked_until`, so fresh login challenges can't be used to
+-- keep guessing.
+ALTER TABLE Users ADD COLUMN totp_failures INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE Users ADD COLUMN totp_locked_until DATETIME NULL;
+
+-- One-time codes to log in without the authenticator, only their hashes are stored. A code
+-- only has to be unique among the codes of its user.
+CREATE TABLE IF NOT EXISTS RecoveryCodes(
+    username text NOT NULL REFERENCES Users(username) ON DELETE CASCADE ON UPDATE CASCADE,
+    code_hash text NOT NULL,
+    used_at DATETIME NULL,
+    PRIMARY KEY (username, code_hash)
+);
+
+-- Logins that passed the password step and wait for the second factor.
+CREATE TABLE IF NOT EXISTS MfaChallenges(
+    token_hash text NOT NULL PRIMARY KEY,
+    username text NOT NULL REFERENCES Users(username) ON DELETE CASCADE ON UPDATE CASCADE,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    expires_at DATETIME NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_mfa_challenges_username ON MfaChallenges(username);
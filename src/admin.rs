@@ -0,0 +1,248 @@
+//! Maintenance commands for operators, run as `realworld-app-dioxus-sqlite admin <command>`
+//! against the database in `DATABASE_URL`.
+use crate::models::{Article, QueuedMail, User};
+
+const USAGE: &str = "usage: realworld-app-dioxus-sqlite admin <command>
+
+commands:
+    create-user <username> <email> [password]   create a user, the password is read from stdin when left out
+    reset-password <username> [password]        set a new password and sign the user out everywhere
+    disable-2fa <username>                      turn off two-factor authentication for a user who lost it
+    delete-user <username>                      delete a user with all their articles, comments and favourites
+    ban <username>                              keep the user's content but block logins
+    unban <username>                            lift a ban
+    delete-article <slug>                       delete an article with its comments and tags
+    comment-history <id>                        print a comment with the bodies it had before its edits
+    mail-queue                                  list the mail waiting to be sent and the mail given up on
+    mail-retry [id]                             queue mail that was given up on again, all of it without an id
+    rebuild-search                              rebuild and optimize the full-text indexes
+    optimize-search                             merge the full-text index segments
+    stats                                       print instance statistics";
+
+pub async fn run(args: &[String]) -> Result<(), String> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["create-user", username, email, password @ ..] => {
+            let password = password_arg(password)?;
+            let user =
+                crate::auth::validate_signup(username.to_string(), email.to_string(), password)?;
+            user.insert().await.map_err(|x| x.to_string())?;
+            User::mark_email_verified(username)
+                .await
+                .map_err(|x| x.to_string())?;
+            println!("created user {username}");
+        }
+        ["reset-password", username, password @ ..] => {
+            let password = password_arg(password)?;
+            User::get(username.to_string())
+                .await
+                .map_err(|_| format!("no user {username}"))?
+                .set_password(password)?
+                .update()
+                .await
+                .map_err(|x| x.to_string())?;
+            crate::auth::revoke_credentials(username)
+                .await
+                .map_err(|x| x.to_string())?;
+            println!("password of {username} changed, all sessions signed out");
+        }
+        ["disable-2fa", username] => {
+            User::get(username.to_string())
+                .await
+                .map_err(|_| format!("no user {username}"))?;
+            crate::models::TwoFactor::disable(username)
+                .await
+                .map_err(|x| x.to_string())?;
+            println!("two-factor authentication of {username} turned off");
+        }
+        ["delete-user", username] => {
+            if User::delete(username)
+                .await
+                .map_err(|x| x.to_string())?
+                .rows_affected()
+                == 0
+            {
+                return Err(format!("no user {username}"));
+            }
+            println!("deleted user {username}");
+        }
+        ["ban", username] => {
+            set_banned(username, true).await?;
+            crate::auth::revoke_credentials(username)
+                .await
+                .map_err(|x| x.to_string())?;
+            println!("banned {username}, all sessions signed out");
+        }
+        ["unban", username] => {
+            set_banned(username, false).await?;
+            println!("lifted the ban of {username}");
+        }
+        ["delete-article", slug] => {
+            let author = Article::author_of(slug)
+                .await
+                .map_err(|_| format!("no article {slug}"))?;
+            Article::delete(slug.to_string(), author)
+                .await
+                .map_err(|x| x.to_string())?;
+            println!("deleted article {slug}");
+        }
+        ["comment-history", id] => {
+            let id = id.parse::<i64>().map_err(|_| format!("no comment {id}"))?;
+            comment_history(id).await?;
+        }
+        ["mail-queue"] => mail_queue().await.map_err(|x| x.to_string())?,
+        ["mail-retry", id @ ..] => {
+            let id = match id {
+                [] => None,
+                [id] => Some(id.parse::<i64>().map_err(|_| format!("no mail {id}"))?),
+                _ => return Err(USAGE.to_string()),
+            };
+            let retried = QueuedMail::retry(id)
+                .await
+                .map_err(|x| x.to_string())?
+                .rows_affected();
+            println!("queued {retried} mail again, a running server sends it within a minute");
+        }
+        ["rebuild-search"] => {
+            crate::models::rebuild_search_index()
+                .await
+                .map_err(|x| x.to_string())?;
+            crate::models::optimize_search_index()
+                .await
+                .map_err(|x| x.to_string())?;
+            println!("rebuilt the search indexes");
+        }
+        ["optimize-search"] => {
+            crate::models::optimize_search_index()
+                .await
+                .map_err(|x| x.to_string())?;
+            println!("optimized the search indexes");
+        }
+        ["stats"] => stats().await.map_err(|x| x.to_string())?,
+        ["help"] | ["--help"] | [] => println!("{USAGE}"),
+        _ => return Err(USAGE.to_string()),
+    }
+    Ok(())
+}
+
+/// The optional trailing password argument, falling back to a line from stdin so it does
+/// not have to end up in the shell history.
+fn password_arg(args: &[&str]) -> Result<String, String> {
+    match args {
+        [password] => Ok(password.to_string()),
+        [] => {
+            eprintln!("password:");
+            let mut password = String::new();
+            std::io::stdin()
+                .read_line(&mut password)
+                .map_err(|x| x.to_string())?;
+            Ok(password.trim_end_matches(['\r', '\n']).to_string())
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+async fn set_banned(username: &str, banned: bool) -> Result<(), String> {
+    if User::set_banned(username, banned)
+        .await
+        .map_err(|x| x.to_string())?
+        .rows_affected()
+        == 0
+    {
+        return Err(format!("no user {username}"));
+    }
+    Ok(())
+}
+
+async fn comment_history(id: i64) -> Result<(), String> {
+    let comment = sqlx::query!(
+        "SELECT article, username, body, created_at, edited_at, deleted FROM Comments WHERE id=$1",
+        id
+    )
+    .fetch_optional(crate::database::server::get_db())
+    .await
+    .map_err(|x| x.to_string())?
+    .ok_or(format!("no comment {id}"))?;
+    let edits = crate::models::Comment::edits(id)
+        .await
+        .map_err(|x| x.to_string())?;
+
+    println!(
+        "comment {id} by {} on {}, posted {}",
+        comment.username, comment.article, comment.created_at
+    );
+    for edit in edits {
+        println!("\n--- replaced {}\n{}", edit.replaced_at, edit.body);
+    }
+    match (comment.deleted, comment.edited_at) {
+        (true, _) => println!("\n--- deleted, kept for its replies\n{}", comment.body),
+        (false, Some(edited_at)) => println!("\n--- current, edited {edited_at}\n{}", comment.body),
+        (false, None) => println!("\n--- current\n{}", comment.body),
+    }
+    Ok(())
+}
+
+async fn mail_queue() -> Result<(), sqlx::Error> {
+    for mail in QueuedMail::all().await? {
+        let state = match mail.next_attempt_at {
+            Some(next) => format!("next attempt {}", next.format(crate::models::DATE_FORMAT)),
+            None => "given up".to_string(),
+        };
+        println!(
+            "{} queued {} to {} \"{}\": {} attempts, {state}",
+            mail.id,
+            mail.created_at.format(crate::models::DATE_FORMAT),
+            mail.recipient,
+            mail.subject,
+            mail.attempts
+        );
+        if let Some(err) = mail.last_error {
+            println!("    {err}");
+        }
+    }
+    Ok(())
+}
+
+async fn stats() -> Result<(), sqlx::Error> {
+    let stats = sqlx::query!(
+        r#"SELECT
+        (SELECT count(*) FROM Users) as "users!: i64",
+        (SELECT count(*) FROM Users WHERE banned_at IS NOT NULL) as "banned!: i64",
+        (SELECT count(*) FROM Articles) as "articles!: i64",
+        (SELECT count(*) FROM Articles WHERE status = 'draft') as "drafts!: i64",
+        (SELECT count(*) FROM Comments WHERE deleted=0) as "comments!: i64",
+        (SELECT count(DISTINCT tag) FROM ArticleTags) as "tags!: i64",
+        (SELECT count(*) FROM FavArticles) as "favourites!: i64",
+        (SELECT count(*) FROM Follows) as "follows!: i64",
+        (SELECT count(*) FROM Sessions WHERE expires_at > CURRENT_TIMESTAMP) as "sessions!: i64",
+        (SELECT max(created_at) FROM Articles WHERE status = 'published') as "last_article: String",
+        (SELECT max(version) FROM _sqlx_migrations WHERE success) as "schema_version: i64",
+        (SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()) as "size!: i64""#
+    )
+    .fetch_one(crate::database::server::get_db())
+    .await?;
+
+    println!(
+        "users:            {} ({} banned)",
+        stats.users, stats.banned
+    );
+    println!(
+        "articles:         {} ({} drafts)",
+        stats.articles, stats.drafts
+    );
+    println!("comments:         {}", stats.comments);
+    println!("tags:             {}", stats.tags);
+    println!("favourites:       {}", stats.favourites);
+    println!("follows:          {}", stats.follows);
+    println!("active sessions:  {}", stats.sessions);
+    println!(
+        "last article:     {}",
+        stats.last_article.unwrap_or("-".to_string())
+    );
+    println!(
+        "schema version:   {}",
+        stats.schema_version.unwrap_or_default()
+    );
+    println!("database size:    {} KiB", stats.size / 1024);
+    Ok(())
+}
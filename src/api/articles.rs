@@ -0,0 +1,386 @@
+use axum::{
+    extract::{rejection::JsonRejection, Path, Query},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{iso_date, ApiError, ApiResult, AuthUser, MaybeAuthUser, Profile};
+use crate::models::{Article, Comment};
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct ArticleEnvelope<T> {
+    article: T,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ArticleList {
+    articles: Vec<ArticleView>,
+    articles_count: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ArticleView {
+    slug: String,
+    title: String,
+    description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+    tag_list: Vec<String>,
+    created_at: String,
+    updated_at: String,
+    favorited: bool,
+    favorites_count: i64,
+    author: Profile,
+}
+
+impl From<Article> for ArticleView {
+    fn from(article: Article) -> Self {
+        Self {
+            slug: article.slug,
+            title: article.title,
+            description: article.description,
+            body: article.body,
+            tag_list: article
+                .tag_list
+                .into_iter()
+                .filter(|tag| !tag.is_empty())
+                .collect(),
+            created_at: iso_date(&article.created_at),
+            updated_at: iso_date(&article.updated_at),
+            favorited: article.fav,
+            favorites_count: article.favorites_count,
+            author: Profile {
+                username: article.author.username,
+                bio: None,
+                image: article.author.image,
+                following: article.author.following,
+            },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct NewArticle {
+    title: String,
+    description: String,
+    body: String,
+    #[serde(default)]
+    tag_list: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UpdateArticle {
+    title: Option<String>,
+    description: Option<String>,
+    body: Option<String>,
+    tag_list: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ListParams {
+    tag: Option<String>,
+    author: Option<String>,
+    favorited: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+impl ListParams {
+    /// The models paginate by page, so an `offset` that is not a multiple of `limit` is
+    /// rounded down to the start of its page.
+    fn page_and_amount(&self) -> (i64, i64) {
+        let amount = self.limit.unwrap_or(20).clamp(1, 100);
+        (self.offset.unwrap_or_default().max(0) / amount, amount)
+    }
+}
+
+fn single(article: Article) -> Json<ArticleEnvelope<ArticleView>> {
+    Json(ArticleEnvelope {
+        article: article.into(),
+    })
+}
+
+fn list_of(articles: Vec<Article>, articles_count: i64) -> Json<ArticleList> {
+    Json(ArticleList {
+        articles: articles.into_iter().map(ArticleView::from).collect(),
+        articles_count,
+    })
+}
+
+pub(crate) async fn list(
+    MaybeAuthUser(logged_user): MaybeAuthUser,
+    Query(params): Query<ListParams>,
+) -> ApiResult<ArticleList> {
+    let (page, amount) = params.page_and_amount();
+    let profile_filter = match (params.author, params.favorited) {
+        (Some(author), _) => Some((author, false)),
+        (None, Some(favorited)) => Some((favorited, true)),
+        (None, None) => None,
+    };
+
+    if let Some((username, favourites)) = profile_filter {
+        let articles = Article::for_user_profile_home(
+            username.clone(),
+            favourites,
+            false,
+            page,
+            amount,
+            logged_user,
+        )
+        .await?;
+        let count = Article::count_for_user_profile_home(username, favourites).await?;
+        Ok(list_of(articles, count))
+    } else {
+        let tag = params.tag.unwrap_or_default();
+        let articles =
+            Article::for_home_page(page, amount, tag.clone(), false, logged_user.clone()).await?;
+        let count = Article::count_for_home_page(tag, false, logged_user).await?;
+        Ok(list_of(articles, count))
+    }
+}
+
+pub(crate) async fn feed(
+    auth: AuthUser,
+    Query(params): Query<ListParams>,
+) -> ApiResult<ArticleList> {
+    let (page, amount) = params.page_and_amount();
+    let articles = Article::for_home_page(
+        page,
+        amount,
+        String::new(),
+        true,
+        Some(auth.username.clone()),
+    )
+    .await?;
+    let count = Article::count_for_home_page(String::new(), true, Some(auth.username)).await?;
+    Ok(list_of(articles, count))
+}
+
+pub(crate) async fn get(
+    MaybeAuthUser(logged_user): MaybeAuthUser,
+    Path(slug): Path<String>,
+) -> ApiResult<ArticleEnvelope<ArticleView>> {
+    Ok(single(Article::for_article(slug, logged_user).await?))
+}
+
+async fn save(
+    author: String,
+    slug: String,
+    title: String,
+    description: String,
+    body: String,
+    tag_list: Vec<String>,
+) -> ApiResult<ArticleEnvelope<ArticleView>> {
+    let article = crate::views::validate_article(title, description, body, tag_list.join(" "))
+        .map_err(|x| ApiError::unprocessable("article", x))?;
+    let slug =
+        crate::views::update_article(author.clone(), slug, article, None, None, None).await?;
+    Ok(single(Article::for_article(slug, Some(author)).await?))
+}
+
+pub(crate) async fn create(
+    auth: AuthUser,
+    payload: Result<Json<ArticleEnvelope<NewArticle>>, JsonRejection>,
+) -> ApiResult<ArticleEnvelope<ArticleView>> {
+    let NewArticle {
+        title,
+        description,
+        body,
+        tag_list,
+    } = payload?.0.article;
+    save(
+        auth.username,
+        String::new(),
+        title,
+        description,
+        body,
+        tag_list,
+    )
+    .await
+}
+
+/// Loads an article the logged user is about to modify, checking ownership.
+async fn owned_article(auth: &AuthUser, slug: String) -> Result<Article, ApiError> {
+    let article = Article::for_article(slug, Some(auth.username.clone())).await?;
+    if article.author.username != auth.username {
+        return Err(ApiError::Forbidden);
+    }
+    Ok(article)
+}
+
+pub(crate) async fn update(
+    auth: AuthUser,
+    Path(slug): Path<String>,
+    payload: Result<Json<ArticleEnvelope<UpdateArticle>>, JsonRejection>,
+) -> ApiResult<ArticleEnvelope<ArticleView>> {
+    let changes = payload?.0.article;
+    let current = owned_article(&auth, slug).await?;
+    save(
+        auth.username,
+        current.slug,
+        changes.title.unwrap_or(current.title),
+        changes.description.unwrap_or(current.description),
+        changes.body.or(current.body).unwrap_or_default(),
+        changes.tag_list.unwrap_or(current.tag_list),
+    )
+    .await
+}
+
+pub(crate) async fn delete(auth: AuthUser, Path(slug): Path<String>) -> Result<(), ApiError> {
+    let article = owned_article(&auth, slug).await?;
+    Article::delete(article.slug, auth.username).await?;
+    Ok(())
+}
+
+/// `toggle_fav` flips the state, so only call it when the state has to change.
+async fn set_favorite(
+    auth: AuthUser,
+    slug: String,
+    fav: bool,
+) -> ApiResult<ArticleEnvelope<ArticleView>> {
+    let article = Article::for_article(slug, Some(auth.username.clone())).await?;
+    if article.fav == fav {
+        return Ok(single(article));
+    }
+    crate::components::toggle_fav(article.slug.clone(), auth.username.clone()).await?;
+    Ok(single(
+        Article::for_article(article.slug, Some(auth.username)).await?,
+    ))
+}
+
+pub(crate) async fn favorite(
+    auth: AuthUser,
+    Path(slug): Path<String>,
+) -> ApiResult<ArticleEnvelope<ArticleView>> {
+    set_favorite(auth, slug, true).await
+}
+
+pub(crate) async fn unfavorite(
+    auth: AuthUser,
+    Path(slug): Path<String>,
+) -> ApiResult<ArticleEnvelope<ArticleView>> {
+    set_favorite(auth, slug, false).await
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CommentView {
+    id: i64,
+    created_at: String,
+    updated_at: String,
+    body: String,
+    author: Profile,
+}
+
+#[derive(Serialize)]
+pub(crate) struct CommentList {
+    comments: Vec<CommentView>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct CommentEnvelope<T> {
+    comment: T,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct NewComment {
+    body: String,
+}
+
+async fn comment_view(
+    comment: Comment,
+    logged_user: Option<&str>,
+) -> Result<CommentView, ApiError> {
+    let following = match logged_user {
+        Some(logged_user) => super::profiles::is_following(logged_user, &comment.username).await?,
+        None => false,
+    };
+    Ok(CommentView {
+        id: comment.id,
+        created_at: iso_date(&comment.created_at),
+        updated_at: iso_date(comment.edited_at.as_deref().unwrap_or(&comment.created_at)),
+        body: comment.body,
+        author: Profile {
+            username: comment.username,
+            bio: None,
+            image: comment.user_image,
+            following,
+        },
+    })
+}
+
+pub(crate) async fn comments(
+    MaybeAuthUser(logged_user): MaybeAuthUser,
+    Path(slug): Path<String>,
+) -> ApiResult<CommentList> {
+    let article = Article::for_article(slug, logged_user.clone()).await?;
+    let mut comments = vec![];
+    // placeholders of deleted comments only matter to the threads of the web UI
+    for comment in Comment::get_all(article.slug)
+        .await?
+        .into_iter()
+        .filter(|x| !x.deleted)
+    {
+        comments.push(comment_view(comment, logged_user.as_deref()).await?);
+    }
+    Ok(Json(CommentList { comments }))
+}
+
+pub(crate) async fn add_comment(
+    auth: AuthUser,
+    Path(slug): Path<String>,
+    payload: Result<Json<CommentEnvelope<NewComment>>, JsonRejection>,
+) -> ApiResult<CommentEnvelope<CommentView>> {
+    let body = payload?.0.comment.body;
+    if body.trim().is_empty() {
+        return Err(ApiError::unprocessable("body", "can't be empty"));
+    }
+    let article = Article::for_article(slug, Some(auth.username.clone())).await?;
+    let id = Comment::insert(article.slug.clone(), auth.username.clone(), body, None)
+        .await?
+        .last_insert_rowid();
+    let comment = Comment::get_all(article.slug)
+        .await?
+        .into_iter()
+        .find(|x| x.id == id)
+        .ok_or(ApiError::NotFound)?;
+    Ok(Json(CommentEnvelope {
+        comment: comment_view(comment, Some(&auth.username)).await?,
+    }))
+}
+
+pub(crate) async fn delete_comment(
+    auth: AuthUser,
+    Path((slug, id)): Path<(String, i32)>,
+) -> Result<(), ApiError> {
+    let comment = Comment::get_all(slug)
+        .await?
+        .into_iter()
+        .find(|x| x.id == i64::from(id) && !x.deleted)
+        .ok_or(ApiError::NotFound)?;
+    if comment.username != auth.username {
+        return Err(ApiError::Forbidden);
+    }
+    Comment::delete(id, auth.username).await?;
+    Ok(())
+}
+
+#[derive(Serialize)]
+pub(crate) struct TagList {
+    tags: Vec<String>,
+}
+
+pub(crate) async fn tags() -> ApiResult<TagList> {
+    let tags = sqlx::query_scalar!(
+        "SELECT DISTINCT tag FROM ArticleTags
+        WHERE article IN (SELECT slug FROM Articles WHERE status = 'published')
+        ORDER BY tag"
+    )
+    .fetch_all(crate::database::server::get_db())
+    .await?;
+    Ok(Json(TagList { tags }))
+}
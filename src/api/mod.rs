@@ -0,0 +1,238 @@
+//! REST endpoints following the RealWorld backend spec, for clients that can't use the
+//! server functions (mobile apps, test scripts). They share the models with the UI so both
+//! see the same data.
+use axum::{
+    extract::{rejection::JsonRejection, FromRequestParts},
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use serde::Serialize;
+
+mod articles;
+mod profiles;
+mod users;
+
+pub fn router<S>() -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/api/users", post(users::register))
+        .route("/api/users/login", post(users::login))
+        .route("/api/users/login/mfa", post(users::login_mfa))
+        .route("/api/user", get(users::current).put(users::update))
+        .route("/api/profiles/{username}", get(profiles::get))
+        .route(
+            "/api/profiles/{username}/follow",
+            post(profiles::follow).delete(profiles::unfollow),
+        )
+        .route("/api/articles", get(articles::list).post(articles::create))
+        .route("/api/articles/feed", get(articles::feed))
+        .route(
+            "/api/articles/{slug}",
+            get(articles::get)
+                .put(articles::update)
+                .delete(articles::delete),
+        )
+        .route(
+            "/api/articles/{slug}/favorite",
+            post(articles::favorite).delete(articles::unfavorite),
+        )
+        .route(
+            "/api/articles/{slug}/comments",
+            get(articles::comments).post(articles::add_comment),
+        )
+        .route(
+            "/api/articles/{slug}/comments/{id}",
+            delete(articles::delete_comment),
+        )
+        .route("/api/tags", get(articles::tags))
+}
+
+#[derive(Debug)]
+pub(crate) enum ApiError {
+    Unauthorized,
+    /// The password was right, the second factor goes to `/api/users/login/mfa` with this
+    /// token.
+    MfaRequired(String),
+    Forbidden,
+    NotFound,
+    Unprocessable(String, String),
+    TooManyRequests(String, String),
+    Internal,
+}
+
+impl ApiError {
+    fn unprocessable(field: &str, message: impl ToString) -> Self {
+        Self::Unprocessable(field.to_string(), message.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, field, message) = match self {
+            Self::MfaRequired(token) => {
+                return (
+                    StatusCode::UNAUTHORIZED,
+                    Json(serde_json::json!({
+                        "errors": { "otp": ["is required"] },
+                        "mfaToken": token,
+                    })),
+                )
+                    .into_response()
+            }
+            Self::Unauthorized => (
+                StatusCode::UNAUTHORIZED,
+                "token".into(),
+                "is missing or invalid".into(),
+            ),
+            Self::Forbidden => (
+                StatusCode::FORBIDDEN,
+                "user".into(),
+                "is not the owner".into(),
+            ),
+            Self::NotFound => (StatusCode::NOT_FOUND, "resource".into(), "not found".into()),
+            Self::Unprocessable(field, message) => {
+                (StatusCode::UNPROCESSABLE_ENTITY, field, message)
+            }
+            Self::TooManyRequests(field, message) => {
+                (StatusCode::TOO_MANY_REQUESTS, field, message)
+            }
+            Self::Internal => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "server".into(),
+                "unexpected error, try again later".into(),
+            ),
+        };
+        (
+            status,
+            Json(serde_json::json!({ "errors": { field: [message] } })),
+        )
+            .into_response()
+    }
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(err: sqlx::Error) -> Self {
+        match err {
+            sqlx::Error::RowNotFound => Self::NotFound,
+            err => {
+                tracing::error!("DB error in REST api: {err:?}");
+                Self::Internal
+            }
+        }
+    }
+}
+
+impl From<crate::views::SaveError> for ApiError {
+    fn from(err: crate::views::SaveError) -> Self {
+        match err {
+            crate::views::SaveError::Conflict => {
+                Self::unprocessable("article", "was changed in the meantime")
+            }
+            crate::views::SaveError::Database(err) => err.into(),
+        }
+    }
+}
+
+impl From<crate::auth::SecondFactorError> for ApiError {
+    fn from(err: crate::auth::SecondFactorError) -> Self {
+        match err {
+            crate::auth::SecondFactorError::Invalid => {
+                Self::unprocessable("code", "is invalid or expired")
+            }
+            crate::auth::SecondFactorError::Locked => Self::TooManyRequests(
+                "otp".to_string(),
+                "is locked after too many wrong codes, try again later".to_string(),
+            ),
+            crate::auth::SecondFactorError::Database(err) => err.into(),
+        }
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        Self::unprocessable("body", rejection.body_text())
+    }
+}
+
+type ApiResult<T> = Result<Json<T>, ApiError>;
+
+/// The spec sends `Authorization: Token <jwt>`; the UI cookie is accepted too.
+fn token_from_parts(parts: &Parts) -> Option<String> {
+    parts
+        .headers
+        .get(header::AUTHORIZATION)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| {
+            x.strip_prefix("Token ")
+                .or_else(|| x.strip_prefix("Bearer "))
+        })
+        .map(str::to_string)
+        .or_else(|| {
+            parts
+                .headers
+                .get(header::COOKIE)
+                .and_then(|x| x.to_str().ok())
+                .and_then(|x| x.split("; ").find_map(|x| x.strip_prefix("token=")))
+                .map(str::to_string)
+        })
+}
+
+/// Extracts the logged in user, rejecting the request when there is none.
+pub(crate) struct AuthUser {
+    pub username: String,
+    pub token: String,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for AuthUser {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let token = token_from_parts(parts).ok_or(ApiError::Unauthorized)?;
+        let claims = crate::auth::session_from_token(&token)
+            .await
+            .ok_or(ApiError::Unauthorized)?;
+        // the auth middleware only sees the cookie, API clients send the token in a header
+        if let Err(err) = crate::models::Session::touch(&claims.jti).await {
+            tracing::error!("could not record session activity: {}", err);
+        }
+        Ok(Self {
+            username: claims.sub,
+            token,
+        })
+    }
+}
+
+/// Same as [`AuthUser`], for endpoints that anonymous users may call too.
+pub(crate) struct MaybeAuthUser(pub Option<String>);
+
+impl<S: Send + Sync> FromRequestParts<S> for MaybeAuthUser {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self(
+            AuthUser::from_request_parts(parts, state)
+                .await
+                .ok()
+                .map(|user| user.username),
+        ))
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct Profile {
+    username: String,
+    bio: Option<String>,
+    image: Option<String>,
+    following: bool,
+}
+
+/// Dates leave the models already formatted for the UI, the spec wants ISO 8601.
+fn iso_date(date: &str) -> String {
+    sqlx::types::chrono::NaiveDateTime::parse_from_str(date, crate::models::DATE_FORMAT)
+        .map(|x| x.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string())
+        .unwrap_or_else(|_| date.to_string())
+}
@@ -0,0 +1,74 @@
+use axum::{extract::Path, Json};
+use serde::Serialize;
+
+use super::{ApiError, ApiResult, AuthUser, MaybeAuthUser, Profile};
+use crate::models::User;
+
+#[derive(Serialize)]
+pub(crate) struct ProfileEnvelope {
+    profile: Profile,
+}
+
+pub(crate) async fn is_following(follower: &str, influencer: &str) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(
+        "SELECT EXISTS(SELECT 1 FROM Follows WHERE follower=$1 and influencer=$2)",
+        follower,
+        influencer
+    )
+    .fetch_one(crate::database::server::get_db())
+    .await
+    .map(|x| x != 0)
+}
+
+async fn profile(username: String, logged_user: Option<String>) -> ApiResult<ProfileEnvelope> {
+    let user = User::get(username).await?;
+    let following = match logged_user {
+        Some(logged_user) => is_following(&logged_user, &user.username()).await?,
+        None => false,
+    };
+    Ok(Json(ProfileEnvelope {
+        profile: Profile {
+            username: user.username(),
+            bio: user.bio(),
+            image: user.image(),
+            following,
+        },
+    }))
+}
+
+pub(crate) async fn get(
+    MaybeAuthUser(logged_user): MaybeAuthUser,
+    Path(username): Path<String>,
+) -> ApiResult<ProfileEnvelope> {
+    profile(username, logged_user).await
+}
+
+/// `toggle_follow` flips the state, so only call it when the state has to change.
+async fn set_following(
+    auth: AuthUser,
+    username: String,
+    follow: bool,
+) -> ApiResult<ProfileEnvelope> {
+    let other = User::get(username).await?.username();
+    if other == auth.username {
+        return Err(ApiError::unprocessable("profile", "cannot follow yourself"));
+    }
+    if is_following(&auth.username, &other).await? != follow {
+        crate::components::toggle_follow(auth.username.clone(), other.clone()).await?;
+    }
+    profile(other, Some(auth.username)).await
+}
+
+pub(crate) async fn follow(
+    auth: AuthUser,
+    Path(username): Path<String>,
+) -> ApiResult<ProfileEnvelope> {
+    set_following(auth, username, true).await
+}
+
+pub(crate) async fn unfollow(
+    auth: AuthUser,
+    Path(username): Path<String>,
+) -> ApiResult<ProfileEnvelope> {
+    set_following(auth, username, false).await
+}
@@ -0,0 +1,224 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{rejection::JsonRejection, ConnectInfo},
+    http::HeaderMap,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{ApiError, ApiResult, AuthUser};
+use crate::auth::ClientInfo;
+use crate::models::User;
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct UserEnvelope<T> {
+    user: T,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct NewUser {
+    username: String,
+    email: String,
+    password: String,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct LoginUser {
+    email: String,
+    password: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MfaLogin {
+    mfa_token: String,
+    code: String,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct UpdateUser {
+    email: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+    image: Option<String>,
+    bio: Option<String>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct AuthenticatedUser {
+    email: String,
+    token: String,
+    username: String,
+    bio: Option<String>,
+    image: Option<String>,
+}
+
+impl AuthenticatedUser {
+    fn new(user: User, token: String) -> UserEnvelope<Self> {
+        UserEnvelope {
+            user: Self {
+                email: user.email(),
+                token,
+                username: user.username(),
+                bio: user.bio(),
+                image: user.image(),
+            },
+        }
+    }
+}
+
+async fn session_token(username: String, client: ClientInfo) -> Result<String, ApiError> {
+    crate::auth::new_session_token(username, client)
+        .await
+        .map_err(|err| {
+            tracing::error!("Token encode error: {}", err);
+            ApiError::Internal
+        })
+}
+
+pub(crate) async fn register(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    payload: Result<Json<UserEnvelope<NewUser>>, JsonRejection>,
+) -> ApiResult<UserEnvelope<AuthenticatedUser>> {
+    let NewUser {
+        username,
+        email,
+        password,
+    } = payload?.0.user;
+    let user = crate::auth::validate_signup(username, email, password)
+        .map_err(|x| ApiError::unprocessable("user", x))?;
+
+    if let Err(x) = user.insert().await {
+        let x = x.to_string();
+        return Err(if x.contains("UNIQUE constraint failed: Users.email") {
+            ApiError::unprocessable("email", "has already been taken")
+        } else if x.contains("UNIQUE constraint failed: Users.username") {
+            ApiError::unprocessable("username", "has already been taken")
+        } else {
+            tracing::error!("error from DB: {}", x);
+            ApiError::Internal
+        });
+    }
+
+    let user = User::get(user.username()).await?;
+    if let Err(x) = crate::auth::send_email_verification(&user, &user.email()).await {
+        tracing::error!(
+            "could not send the confirmation of {}: {x}",
+            user.username()
+        );
+    }
+    let token = session_token(user.username(), ClientInfo::new(&headers, peer)).await?;
+    Ok(Json(AuthenticatedUser::new(user, token)))
+}
+
+pub(crate) async fn login(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    payload: Result<Json<UserEnvelope<LoginUser>>, JsonRejection>,
+) -> ApiResult<UserEnvelope<AuthenticatedUser>> {
+    let LoginUser { email, password } = payload?.0.user;
+    let invalid = || ApiError::unprocessable("email or password", "is invalid");
+
+    let user = User::get_email(email).await.map_err(|_| invalid())?;
+    if !crate::auth::password_matches(&user.username(), &password).await {
+        return Err(invalid());
+    }
+    if crate::models::TwoFactor::secret(&user.username())
+        .await?
+        .is_some()
+    {
+        let token = crate::auth::new_mfa_challenge(&user.username()).await?;
+        return Err(ApiError::MfaRequired(token));
+    }
+    let token = session_token(user.username(), ClientInfo::new(&headers, peer)).await?;
+    Ok(Json(AuthenticatedUser::new(user, token)))
+}
+
+/// Second step of the login of users with two-factor authentication.
+pub(crate) async fn login_mfa(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    payload: Result<Json<UserEnvelope<MfaLogin>>, JsonRejection>,
+) -> ApiResult<UserEnvelope<AuthenticatedUser>> {
+    let MfaLogin { mfa_token, code } = payload?.0.user;
+    let username = crate::auth::complete_mfa_challenge(&mfa_token, &code).await?;
+    let user = User::get(username).await?;
+    let token = session_token(user.username(), ClientInfo::new(&headers, peer)).await?;
+    Ok(Json(AuthenticatedUser::new(user, token)))
+}
+
+pub(crate) async fn current(auth: AuthUser) -> ApiResult<UserEnvelope<AuthenticatedUser>> {
+    let user = User::get(auth.username)
+        .await
+        .map_err(|_| ApiError::Unauthorized)?;
+    Ok(Json(AuthenticatedUser::new(user, auth.token)))
+}
+
+pub(crate) async fn update(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    auth: AuthUser,
+    payload: Result<Json<UserEnvelope<UpdateUser>>, JsonRejection>,
+) -> ApiResult<UserEnvelope<AuthenticatedUser>> {
+    let changes = payload?.0.user;
+    let password_changed = changes.password.is_some();
+    let mut user = User::get(auth.username)
+        .await
+        .map_err(|_| ApiError::Unauthorized)?;
+
+    if changes
+        .username
+        .is_some_and(|username| username != user.username())
+    {
+        return Err(ApiError::unprocessable("username", "cannot be changed"));
+    }
+    if let Some(password) = changes.password {
+        user = user
+            .set_password(password)
+            .map_err(|x| ApiError::unprocessable("password", x))?;
+    }
+    if let Some(bio) = changes.bio {
+        user = user
+            .set_bio(bio)
+            .map_err(|x| ApiError::unprocessable("bio", x))?;
+    }
+    if let Some(image) = changes.image {
+        user = user
+            .set_image(image)
+            .map_err(|x| ApiError::unprocessable("image", x))?;
+    }
+
+    // a new address only replaces the current one once it is confirmed
+    if let Some(email) = changes.email {
+        use crate::auth::EmailChangeError;
+        crate::auth::change_email(&user, &email)
+            .await
+            .map_err(|x| match x {
+                EmailChangeError::Invalid(x) => ApiError::unprocessable("email", x),
+                EmailChangeError::Taken => {
+                    ApiError::unprocessable("email", "has already been taken")
+                }
+                EmailChangeError::TooSoon => ApiError::TooManyRequests(
+                    "email".to_string(),
+                    "got too many confirmation links lately, try again later".to_string(),
+                ),
+                EmailChangeError::Failed(x) => {
+                    tracing::error!("could not change the email of {}: {x}", user.username());
+                    ApiError::Internal
+                }
+            })?;
+    }
+    user.update().await?;
+
+    // a new password signs out every session, hand the caller a fresh token instead
+    let token = if password_changed {
+        crate::auth::revoke_credentials(&user.username()).await?;
+        session_token(user.username(), ClientInfo::new(&headers, peer)).await?
+    } else {
+        auth.token
+    };
+    let user = User::get(user.username()).await?;
+    Ok(Json(AuthenticatedUser::new(user, token)))
+}
@@ -1,5 +1,3 @@
-#[cfg(feature = "server")]
-use argon2::{password_hash::PasswordVerifier, Argon2};
 use dioxus::fullstack::{SetCookie, SetHeader};
 use dioxus::prelude::*;
 
@@ -33,7 +31,13 @@ pub async fn signup_action(
 ) -> Result<SignupResponse, ServerFnError> {
     match validate_signup(username.clone(), email, password.clone()) {
         Ok(user) => match user.insert().await {
-            Ok(_) => Ok(SignupResponse::Success),
+            Ok(_) => {
+                // the account works without it, the link can be sent again from the settings
+                if let Err(x) = super::send_email_verification(&user, &user.email()).await {
+                    tracing::error!("could not send the confirmation of {username}: {x}");
+                }
+                Ok(SignupResponse::Success)
+            }
             Err(x) => {
                 let x = x.to_string();
                 Ok(if x.contains("UNIQUE constraint failed: Users.email") {
@@ -54,7 +58,7 @@ pub async fn signup_action(
 
 #[post("/api/update_per_page_amount", header: TypedHeader<Cookie>)]
 pub async fn update_per_page_amount(amount: u32) -> Result<(), ServerFnError> {
-    let Some(username) = super::get_username_from_cookie(header) else {
+    let Some(username) = super::get_username_from_cookie(header).await else {
         return Err(ServerFnError::new("not logged in"));
     };
 
@@ -76,7 +80,7 @@ pub async fn update_per_page_amount(amount: u32) -> Result<(), ServerFnError> {
 
 #[post("/api/update_theme_mode", header: TypedHeader<Cookie>)]
 pub async fn update_theme_mode(theme: String) -> Result<(), ServerFnError> {
-    let Some(username) = super::get_username_from_cookie(header) else {
+    let Some(username) = super::get_username_from_cookie(header).await else {
         return Err(ServerFnError::new("not logged in"));
     };
 
@@ -96,80 +100,120 @@ pub async fn update_theme_mode(theme: String) -> Result<(), ServerFnError> {
     Ok(())
 }
 
-#[post("/api/logout", _header: TypedHeader<Cookie>)]
+#[post("/api/logout", header: TypedHeader<Cookie>)]
 pub async fn logout() -> Result<SetHeader<SetCookie>> {
-    Ok(SetHeader::new(format!(
-        "token=; path=/; expires=Thu, 01 Jan 1970 00:00:00 GMT"
-    ))?)
+    if let Some(token) = header.get("token") {
+        super::end_session(token).await;
+    }
+    Ok(SetHeader::new(
+        "token=; path=/; expires=Thu, 01 Jan 1970 00:00:00 GMT",
+    )?)
 }
 
-#[post("/api/login")]
+#[post("/api/login", headers: dioxus::fullstack::HeaderMap, peer: axum::extract::ConnectInfo<std::net::SocketAddr>)]
 pub async fn login(username: String, password: String) -> ServerFnResult<SetHeader<SetCookie>> {
-    let hash_pass_row = match sqlx::query!("SELECT password FROM Users where username=$1", username)
-        .fetch_one(crate::database::server::get_db())
+    if !super::password_matches(&username, &password).await {
+        return Err(ServerFnError::ServerError {
+            message: "Invalid username or password".to_string(),
+            code: 401,
+            details: serde_json::json!("Invalid username or password").into(),
+        });
+    }
+
+    let unexpected = |err: String| {
+        tracing::error!("login of {username} failed: {err}");
+        ServerFnError::new("Unexpected error occured while login, please try later")
+    };
+    let two_factor = crate::models::TwoFactor::secret(&username)
         .await
-    {
-        Ok(row) => row,
+        .map_err(|x| unexpected(x.to_string()))?;
+    if two_factor.is_some() {
+        // no session yet, only a short-lived token for the second step
+        let token = super::new_mfa_challenge(&username)
+            .await
+            .map_err(|x| match x {
+                super::SecondFactorError::Database(x) => unexpected(x.to_string()),
+                x => second_factor_error(x),
+            })?;
+        return SetHeader::new(format!(
+            "{}={token}; path=/; HttpOnly; Max-Age={}",
+            super::MFA_COOKIE,
+            super::MFA_CHALLENGE_TTL
+        ))
+        .map_err(|x| unexpected(x.to_string()));
+    }
+    session_cookie(username, super::ClientInfo::new(&headers, peer.0)).await
+}
+
+/// Second step of the login of a user with two-factor authentication, `code` comes from
+/// their authenticator app or is one of their recovery codes.
+#[post("/api/login_mfa", headers: dioxus::fullstack::HeaderMap, peer: axum::extract::ConnectInfo<std::net::SocketAddr>)]
+pub async fn login_mfa(code: String) -> ServerFnResult<SetHeader<SetCookie>> {
+    let token = super::mfa_token_from_headers(&headers)
+        .ok_or(super::SecondFactorError::Invalid)
+        .map_err(second_factor_error)?;
+    let username = super::complete_mfa_challenge(&token, &code)
+        .await
+        .map_err(|x| match x {
+            super::SecondFactorError::Database(x) => {
+                tracing::error!("could not check the second factor: {x}");
+                ServerFnError::new("Unexpected error occured while login, please try later")
+            }
+            x => second_factor_error(x),
+        })?;
+    session_cookie(username, super::ClientInfo::new(&headers, peer.0)).await
+}
+
+/// A wrong or expired code answers 401, a locked second factor 429, with the message for
+/// the login page.
+#[cfg(feature = "server")]
+fn second_factor_error(err: super::SecondFactorError) -> ServerFnError {
+    let code = match err {
+        super::SecondFactorError::Locked => 429,
+        _ => 401,
+    };
+    ServerFnError::ServerError {
+        message: err.to_string(),
+        code,
+        details: serde_json::json!(err.to_string()).into(),
+    }
+}
+
+/// Opens a session for `username` and hands it out as the auth cookie.
+#[cfg(feature = "server")]
+async fn session_cookie(
+    username: String,
+    client: super::ClientInfo,
+) -> ServerFnResult<SetHeader<SetCookie>> {
+    let token = match super::new_session_token(username, client).await {
+        Ok(token) => token,
         Err(err) => {
-            tracing::error!("DB err: {}", err);
-            return Err(ServerFnError::ServerError {
-                message: "Invalid username or password".to_string(),
-                code: 401,
-                details: serde_json::json!("Invalid username or password").into(),
-            });
+            tracing::error!("Token encode error: {}", err);
+            return Err(ServerFnError::new(
+                "Unexpected error occured while login, please try later",
+            ));
         }
     };
-
-    let parsed_hash = match argon2::password_hash::PasswordHash::new(&hash_pass_row.password) {
-        Ok(hash) => hash,
+    let header = match SetHeader::new(format!(
+        "token={}; path=/; HttpOnly; Max-Age={}",
+        token,
+        super::session_ttl_secs()
+    )) {
+        Ok(h) => h,
         Err(err) => {
-            tracing::error!("Failed to hash password: {}", err);
+            tracing::error!("failed to construct SetHeader: {}", err);
             return Err(ServerFnError::new(
                 "Unexpected error occured while login, please try later",
             ));
         }
     };
-
-    let argon2 = Argon2::default();
-    if argon2
-        .verify_password(password.as_bytes(), &parsed_hash)
-        .is_ok()
-    {
-        let token = match crate::auth::encode_token(crate::auth::TokenClaims {
-            sub: username,
-            exp: (sqlx::types::chrono::Utc::now().timestamp() as usize) + 3_600_000,
-        }) {
-            Ok(token) => token,
-            Err(err) => {
-                tracing::error!("Token encode error: {}", err);
-                return Err(ServerFnError::new(
-                    "Unexpected error occured while login, please try later",
-                ));
-            }
-        };
-        let header = match SetHeader::new(format!("token={}; path=/; HttpOnly", token)) {
-            Ok(h) => h,
-            Err(err) => {
-                tracing::error!("failed to construct SetHeader: {}", err);
-                return Err(ServerFnError::new(
-                    "Unexpected error occured while login, please try later",
-                ));
-            }
-        };
-        Ok(header)
-    } else {
-        Err(ServerFnError::ServerError {
-            message: "Invalid username or password".to_string(),
-            code: 401,
-            details: serde_json::json!("Invalid username or password").into(),
-        })
-    }
+    Ok(header)
 }
 
 #[tracing::instrument]
 #[post("/api/current_user", header: TypedHeader<Cookie>)]
 pub async fn current_user() -> Result<Option<crate::models::User>, ServerFnError> {
-    let Some(logged_user) = super::get_username_from_cookie(header) else {
+    let Some(logged_user) = super::get_username_from_cookie(header).await else {
         return Ok(None);
     };
     Ok(crate::models::User::get(logged_user)
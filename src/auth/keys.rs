@@ -0,0 +1,157 @@
+//! Keys for signing and checking session tokens, read from the environment at startup:
+//!
+//! - `JWT_ALGORITHM`: `HS256` (default), `EdDSA` or `RS256`.
+//! - `JWT_KID`: id of the key new tokens are signed with, `default` when unset.
+//! - `JWT_SECRET`: the current secret for `HS256`.
+//! - `JWT_PRIVATE_KEY_FILE` and `JWT_PUBLIC_KEY_FILE`: the current PEM key pair for `EdDSA`
+//!   and `RS256`.
+//! - `JWT_RETIRED_KEYS`: comma separated `kid=alg:secret` entries for `HS256`, `kid=alg:path`
+//!   to a public PEM file for `EdDSA` and `RS256`. Without the `alg:` prefix the entry uses
+//!   `JWT_ALGORITHM`. Tokens signed with them keep validating until they expire, so the
+//!   current key, or the algorithm, can be changed without logging everybody out.
+use std::collections::HashMap;
+
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+
+struct Keys {
+    algorithm: Algorithm,
+    kid: String,
+    encoding: EncodingKey,
+    /// Keys accepted by `kid`, each with the algorithm its tokens were signed with.
+    decoding: HashMap<String, (Algorithm, DecodingKey)>,
+}
+
+static KEYS: std::sync::OnceLock<Keys> = std::sync::OnceLock::new();
+
+fn env_var(name: &str) -> Result<String, String> {
+    std::env::var(name).map_err(|_| format!("{name} is not set"))
+}
+
+fn read_pem(path: &str) -> Result<Vec<u8>, String> {
+    std::fs::read(path).map_err(|x| format!("could not read key file {path}: {x}"))
+}
+
+fn parse_algorithm(name: &str) -> Option<Algorithm> {
+    match name {
+        "HS256" => Some(Algorithm::HS256),
+        "EdDSA" => Some(Algorithm::EdDSA),
+        "RS256" => Some(Algorithm::RS256),
+        _ => None,
+    }
+}
+
+/// `value` is the secret for `HS256`, the path to the public PEM file otherwise.
+fn decoding_key(algorithm: Algorithm, value: &str) -> Result<DecodingKey, String> {
+    match algorithm {
+        Algorithm::EdDSA => DecodingKey::from_ed_pem(&read_pem(value)?),
+        Algorithm::RS256 => DecodingKey::from_rsa_pem(&read_pem(value)?),
+        _ => Ok(DecodingKey::from_secret(value.as_bytes())),
+    }
+    .map_err(|x| format!("invalid public key {value}: {x}"))
+}
+
+impl Keys {
+    fn from_env() -> Result<Self, String> {
+        let algorithm = match std::env::var("JWT_ALGORITHM") {
+            Err(_) => Algorithm::HS256,
+            Ok(x) => parse_algorithm(&x).ok_or_else(|| {
+                format!("unsupported JWT_ALGORITHM {x}, use HS256, EdDSA or RS256")
+            })?,
+        };
+        let kid = std::env::var("JWT_KID").unwrap_or_else(|_| "default".to_string());
+
+        let (encoding, current) = match algorithm {
+            Algorithm::EdDSA | Algorithm::RS256 => {
+                let private = env_var("JWT_PRIVATE_KEY_FILE")?;
+                let pem = read_pem(&private)?;
+                let encoding = if algorithm == Algorithm::EdDSA {
+                    EncodingKey::from_ed_pem(&pem)
+                } else {
+                    EncodingKey::from_rsa_pem(&pem)
+                }
+                .map_err(|x| format!("invalid private key {private}: {x}"))?;
+                (
+                    encoding,
+                    decoding_key(algorithm, &env_var("JWT_PUBLIC_KEY_FILE")?)?,
+                )
+            }
+            _ => {
+                let secret = env_var("JWT_SECRET")?;
+                (
+                    EncodingKey::from_secret(secret.as_bytes()),
+                    decoding_key(algorithm, &secret)?,
+                )
+            }
+        };
+
+        let mut decoding = HashMap::from([(kid.clone(), (algorithm, current))]);
+        for entry in std::env::var("JWT_RETIRED_KEYS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|x| !x.is_empty())
+        {
+            let Some((retired_kid, value)) = entry.split_once('=') else {
+                return Err(format!("JWT_RETIRED_KEYS entry without a kid: {entry}"));
+            };
+            let (retired_algorithm, value) = match value
+                .split_once(':')
+                .and_then(|(name, rest)| Some((parse_algorithm(name)?, rest)))
+            {
+                Some(x) => x,
+                None => (algorithm, value),
+            };
+            if retired_kid != kid {
+                decoding.insert(
+                    retired_kid.to_string(),
+                    (retired_algorithm, decoding_key(retired_algorithm, value)?),
+                );
+            }
+        }
+
+        Ok(Self {
+            algorithm,
+            kid,
+            encoding,
+            decoding,
+        })
+    }
+}
+
+/// Loads the keys, the server refuses to start with a broken configuration.
+pub fn init_keys() -> Result<(), String> {
+    let keys = Keys::from_env()?;
+    tracing::info!(
+        "signing tokens with {:?} key {:?}, {} key(s) accepted",
+        keys.algorithm,
+        keys.kid,
+        keys.decoding.len()
+    );
+    KEYS.set(keys)
+        .map_err(|_| "JWT keys already loaded".to_string())
+}
+
+fn keys() -> &'static Keys {
+    KEYS.get().expect("JWT keys are loaded at startup")
+}
+
+pub(crate) fn sign<T: serde::Serialize>(claims: &T) -> jsonwebtoken::errors::Result<String> {
+    let keys = keys();
+    let mut header = Header::new(keys.algorithm);
+    header.kid = Some(keys.kid.clone());
+    jsonwebtoken::encode(&header, claims, &keys.encoding)
+}
+
+/// Tokens without a `kid` predate key rotation and are checked against the current key.
+pub(crate) fn verify<T: serde::de::DeserializeOwned>(
+    token: &str,
+) -> jsonwebtoken::errors::Result<jsonwebtoken::TokenData<T>> {
+    let keys = keys();
+    let kid = jsonwebtoken::decode_header(token)?
+        .kid
+        .unwrap_or_else(|| keys.kid.clone());
+    let Some((algorithm, key)) = keys.decoding.get(&kid) else {
+        return Err(jsonwebtoken::errors::ErrorKind::InvalidToken.into());
+    };
+    jsonwebtoken::decode(token, key, &Validation::new(*algorithm))
+}
@@ -1,6 +1,14 @@
 mod api;
 pub use api::*;
 #[cfg(feature = "server")]
+mod keys;
+#[cfg(feature = "server")]
+pub use keys::init_keys;
+#[cfg(feature = "server")]
 mod server;
 #[cfg(feature = "server")]
 pub use server::*;
+#[cfg(feature = "server")]
+mod two_factor;
+#[cfg(feature = "server")]
+pub(crate) use two_factor::*;
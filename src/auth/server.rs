@@ -2,7 +2,6 @@ use axum::{
     http::{header, Request, StatusCode},
     response::Response,
 };
-use jsonwebtoken::{decode, DecodingKey, Validation};
 use serde::{Deserialize, Serialize};
 
 #[cfg(feature = "server")]
@@ -13,6 +12,7 @@ static AUTH_COOKIE: &str = "token";
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TokenClaims {
     pub sub: String, // Optional. Subject (whom token refers to)
+    pub jti: String, // Id of the session in the Sessions table, revoking it invalidates the token
     pub exp: usize, // Required (validate_exp defaults to true in validation). Expiration time (as UTC timestamp)
                     // aud: String,         // Optional. Audience
                     // iat: usize,          // Optional. Issued at (as UTC timestamp)
@@ -27,12 +27,17 @@ pub async fn auth_middleware(
     req: Request<axum::body::Body>,
     next: axum::middleware::Next,
 ) -> Response {
-    match get_username_from_headers(req.headers()) {
-        Some(username) => {
+    match get_session_from_headers(req.headers()).await {
+        Some(TokenClaims {
+            sub: username, jti, ..
+        }) => {
             let Ok(_) = crate::models::User::get(username.clone()).await else {
                 tracing::info!("no user associated with this token {:?}", username);
                 return redirect(req, next).await;
             };
+            if let Err(err) = crate::models::Session::touch(&jti).await {
+                tracing::error!("could not record session activity: {}", err);
+            }
 
             let path = req.uri().path();
             if path.starts_with("/login") || path.starts_with("/signup") {
@@ -71,43 +76,450 @@ async fn redirect(req: Request<axum::body::Body>, next: axum::middleware::Next)
 pub(crate) fn decode_token(
     token: &str,
 ) -> Result<jsonwebtoken::TokenData<TokenClaims>, jsonwebtoken::errors::Error> {
-    let secret = env!("JWT_SECRET");
+    super::keys::verify(token)
+}
+
+pub(crate) fn encode_token(token_claims: TokenClaims) -> jsonwebtoken::errors::Result<String> {
+    super::keys::sign(&token_claims)
+}
+
+/// How long a login stays valid, `SESSION_TTL_HOURS` in the environment, 30 days by default.
+pub(crate) fn session_ttl_secs() -> i64 {
+    static SESSION_TTL: std::sync::OnceLock<i64> = std::sync::OnceLock::new();
+    *SESSION_TTL.get_or_init(|| {
+        std::env::var("SESSION_TTL_HOURS")
+            .ok()
+            .and_then(|x| x.parse::<i64>().ok())
+            .filter(|x| *x > 0)
+            .unwrap_or(24 * 30)
+            * 3600
+    })
+}
+
+/// Where a login comes from, kept with the session so users can tell their devices apart.
+#[derive(Debug)]
+pub(crate) struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip: String,
+}
+
+/// An address and the length of its network prefix, a single address has all the bits.
+type IpRange = (std::net::IpAddr, u32);
+
+/// Reverse proxies whose forwarding headers are believed, `TRUSTED_PROXIES` in the
+/// environment: addresses or CIDR ranges separated by commas. None by default.
+fn trusted_proxies() -> &'static [IpRange] {
+    static TRUSTED_PROXIES: std::sync::OnceLock<Vec<IpRange>> = std::sync::OnceLock::new();
+    TRUSTED_PROXIES
+        .get_or_init(|| parse_ip_ranges(&std::env::var("TRUSTED_PROXIES").unwrap_or_default()))
+}
+
+fn parse_ip_ranges(list: &str) -> Vec<IpRange> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .filter_map(|x| {
+            let (ip, prefix) = match x.split_once('/') {
+                Some((ip, prefix)) => (ip, Some(prefix)),
+                None => (x, None),
+            };
+            let ip = ip.parse::<std::net::IpAddr>().ok();
+            let bits = if ip.is_some_and(|x| x.is_ipv4()) {
+                32
+            } else {
+                128
+            };
+            let prefix = match prefix {
+                Some(x) => x.parse::<u32>().ok().filter(|x| *x <= bits),
+                None => Some(bits),
+            };
+            let range = ip.zip(prefix);
+            if range.is_none() {
+                tracing::error!("ignoring the invalid trusted proxy {x}");
+            }
+            range
+        })
+        .collect()
+}
+
+fn is_trusted_proxy(proxies: &[IpRange], ip: std::net::IpAddr) -> bool {
+    use std::net::IpAddr;
 
-    decode::<TokenClaims>(
-        token,
-        &DecodingKey::from_secret(secret.as_bytes()),
-        &Validation::default(),
+    // an IPv4 proxy seen over an IPv6 socket
+    let ip = match ip {
+        IpAddr::V6(x) => x.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+        x => x,
+    };
+    proxies.iter().any(|(range, prefix)| match (range, ip) {
+        (IpAddr::V4(range), IpAddr::V4(ip)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            u32::from(*range) & mask == u32::from(ip) & mask
+        }
+        (IpAddr::V6(range), IpAddr::V6(ip)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+            u128::from(*range) & mask == u128::from(ip) & mask
+        }
+        _ => false,
+    })
+}
+
+impl ClientInfo {
+    /// Behind a reverse proxy the peer address is the proxy, so forwarding headers win, as
+    /// long as the peer is one of the [`trusted_proxies`]; anyone else could make them up.
+    pub(crate) fn new(headers: &axum::http::HeaderMap, peer: std::net::SocketAddr) -> Self {
+        Self::behind(headers, peer, trusted_proxies())
+    }
+
+    fn behind(
+        headers: &axum::http::HeaderMap,
+        peer: std::net::SocketAddr,
+        proxies: &[IpRange],
+    ) -> Self {
+        let header_value = |name| {
+            headers
+                .get(name)
+                .and_then(|x: &axum::http::HeaderValue| x.to_str().ok())
+                .map(str::trim)
+                .filter(|x| !x.is_empty())
+        };
+        let ip = if is_trusted_proxy(proxies, peer.ip()) {
+            // every proxy appends the address it got the request from, the first one that
+            // is not a trusted proxy is the client
+            header_value("x-forwarded-for")
+                .and_then(|x| {
+                    let hops = x
+                        .split(',')
+                        .filter_map(|x| x.trim().parse::<std::net::IpAddr>().ok())
+                        .collect::<Vec<_>>();
+                    hops.iter()
+                        .rev()
+                        .find(|x| !is_trusted_proxy(proxies, **x))
+                        .or(hops.first())
+                        .copied()
+                })
+                .or_else(|| header_value("x-real-ip").and_then(|x| x.parse().ok()))
+                .unwrap_or(peer.ip())
+        } else {
+            peer.ip()
+        };
+        Self {
+            user_agent: header_value(header::USER_AGENT.as_str()).map(str::to_string),
+            ip: ip.to_string(),
+        }
+    }
+}
+
+/// Records a new session and signs the token handed out after a successful login, for the
+/// cookie and the REST API alike.
+pub(crate) async fn new_session_token(
+    username: String,
+    client: ClientInfo,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let exp = sqlx::types::chrono::Utc::now().timestamp() + session_ttl_secs();
+    let jti = uuid::Uuid::now_v7().to_string();
+
+    if let Err(err) = crate::models::Session::delete_expired().await {
+        tracing::error!("could not clean up expired sessions: {}", err);
+    }
+    crate::models::Session::insert(
+        jti.clone(),
+        username.clone(),
+        exp,
+        client.user_agent,
+        Some(client.ip),
     )
+    .await?;
+    Ok(encode_token(TokenClaims {
+        sub: username,
+        jti,
+        exp: exp as usize,
+    })?)
 }
 
-pub(crate) fn encode_token(token_claims: TokenClaims) -> jsonwebtoken::errors::Result<String> {
-    let secret = env!("JWT_SECRET");
+/// Revokes the session behind `token`, a no-op for invalid tokens.
+pub(crate) async fn end_session(token: &str) {
+    let Ok(jwt) = decode_token(token) else {
+        return;
+    };
+    if let Err(err) = crate::models::Session::delete(&jwt.claims.jti, &jwt.claims.sub).await {
+        tracing::error!("could not revoke session: {}", err);
+    }
+}
+
+/// The claims of `token`, as long as the signature is valid and the session was not revoked.
+pub(crate) async fn session_from_token(token: &str) -> Option<TokenClaims> {
+    let claims = decode_token(token).ok()?.claims;
+    match crate::models::Session::is_active(&claims.jti, &claims.sub).await {
+        Ok(true) => Some(claims),
+        Ok(false) => None,
+        Err(err) => {
+            tracing::error!("could not check session: {}", err);
+            None
+        }
+    }
+}
+
+pub(crate) async fn username_from_token(token: &str) -> Option<String> {
+    session_from_token(token).await.map(|claims| claims.sub)
+}
+
+/// Everything handed out for the old password stops working: sessions, reset links and
+/// logins waiting for their second factor.
+pub(crate) async fn revoke_credentials(username: &str) -> Result<(), sqlx::Error> {
+    crate::models::Session::delete_all(username).await?;
+    crate::models::PasswordReset::delete_all(username).await?;
+    crate::models::MfaChallenge::delete_all(username).await?;
+    Ok(())
+}
+
+/// How long an emailed confirmation link stays usable, in seconds.
+const EMAIL_VERIFICATION_TTL: i64 = 24 * 3_600;
+
+/// Confirmation links sent per user and hour, on top of one a minute, so the app can't be
+/// used to flood someone else's inbox.
+const EMAIL_VERIFICATIONS_PER_HOUR: i64 = 5;
+
+/// Whether `username` got a confirmation link too recently, or too many of them lately, to
+/// get another one now.
+pub(crate) async fn email_verification_throttled(username: &str) -> Result<bool, sqlx::Error> {
+    use crate::models::EmailVerification;
 
-    jsonwebtoken::encode(
-        &jsonwebtoken::Header::default(),
-        &token_claims,
-        &jsonwebtoken::EncodingKey::from_secret(secret.as_bytes()),
+    Ok(EmailVerification::sent_recently(username).await?
+        || EmailVerification::sent_last_hour(username).await? >= EMAIL_VERIFICATIONS_PER_HOUR)
+}
+
+/// Emails `user` a link confirming `email`, their current address or the one they want
+/// instead. Nothing is sent while [`email_verification_throttled`].
+pub(crate) async fn send_email_verification(
+    user: &crate::models::User,
+    email: &str,
+) -> Result<(), String> {
+    let throttled = email_verification_throttled(&user.username())
+        .await
+        .map_err(|x| format!("could not count the confirmation links: {x}"))?;
+    if throttled {
+        return Err(format!(
+            "too many confirmation links for {} lately",
+            user.username()
+        ));
+    }
+    let (token, token_hash) = new_reset_token();
+    let expires_at = sqlx::types::chrono::Utc::now().timestamp() + EMAIL_VERIFICATION_TTL;
+    crate::models::EmailVerification::insert(&token_hash, &user.username(), email, expires_at)
+        .await
+        .map_err(|x| format!("could not store the confirmation link: {x}"))?;
+    let url = format!("{}/verify_email?token={token}", crate::public_url());
+    let mail = crate::mailer::compose(
+        "verify_email",
+        &user.locale(),
+        email.to_string(),
+        &[
+            ("username", user.username()),
+            ("email", email.to_string()),
+            ("url", url),
+        ],
+    )?;
+    crate::mailer::queue_until(mail, expires_at)
+        .await
+        .map_err(|x| format!("could not queue the confirmation mail: {x}"))
+}
+
+#[derive(Debug)]
+pub(crate) enum EmailChangeError {
+    Invalid(String),
+    Taken,
+    /// Too many confirmation links went out lately.
+    TooSoon,
+    Failed(String),
+}
+
+impl std::fmt::Display for EmailChangeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Invalid(x) => write!(f, "{x}"),
+            Self::Taken => write!(f, "This email is already used by another account"),
+            Self::TooSoon => write!(
+                f,
+                "Too many confirmation links were sent lately, try again later"
+            ),
+            Self::Failed(x) => write!(f, "{x}"),
+        }
+    }
+}
+
+/// Keeps `email` aside until its owner confirms it from the link sent to it, the current
+/// address stays in use until then. Nothing happens for the current address.
+pub(crate) async fn change_email(
+    user: &crate::models::User,
+    email: &str,
+) -> Result<(), EmailChangeError> {
+    use crate::models::User;
+
+    if email == user.email() {
+        return Ok(());
+    }
+    if !User::validate_email(email) {
+        return Err(EmailChangeError::Invalid(format!(
+            "The email {email} is invalid, provide a correct one"
+        )));
+    }
+    if User::get_email(email.to_string()).await.is_ok() {
+        return Err(EmailChangeError::Taken);
+    }
+    let throttled = email_verification_throttled(&user.username())
+        .await
+        .map_err(|x| EmailChangeError::Failed(x.to_string()))?;
+    if throttled {
+        return Err(EmailChangeError::TooSoon);
+    }
+    User::update_pending_email(&user.username(), Some(email))
+        .await
+        .map_err(|x| EmailChangeError::Failed(x.to_string()))?;
+    send_email_verification(user, email)
+        .await
+        .map_err(EmailChangeError::Failed)
+}
+
+/// A random token for a reset link, returned together with the hash that gets stored.
+pub(crate) fn new_reset_token() -> (String, String) {
+    use argon2::password_hash::rand_core::{OsRng, RngCore};
+
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    let token = to_hex(&bytes);
+    let hash = hash_reset_token(&token);
+    (token, hash)
+}
+
+/// The token is random with enough entropy, a plain SHA-256 is enough to store it.
+pub(crate) fn hash_reset_token(token: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    to_hex(&Sha256::digest(token.as_bytes()))
+}
+
+pub(super) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|x| format!("{x:02x}")).collect()
+}
+
+/// Checks `password` against the argon2 hash stored for `username`, banned users never match.
+pub(crate) async fn password_matches(username: &str, password: &str) -> bool {
+    use argon2::{password_hash::PasswordVerifier, Argon2};
+
+    let hash_pass_row = match sqlx::query!(
+        "SELECT password FROM Users where username=$1 and banned_at IS NULL",
+        username
     )
+    .fetch_one(crate::database::server::get_db())
+    .await
+    {
+        Ok(row) => row,
+        Err(err) => {
+            tracing::error!("DB err: {}", err);
+            return false;
+        }
+    };
+
+    match argon2::password_hash::PasswordHash::new(&hash_pass_row.password) {
+        Ok(parsed_hash) => Argon2::default()
+            .verify_password(password.as_bytes(), &parsed_hash)
+            .is_ok(),
+        Err(err) => {
+            tracing::error!("Failed to parse password hash: {}", err);
+            false
+        }
+    }
 }
 
 #[tracing::instrument]
-pub(crate) fn get_username_from_headers(headers: &axum::http::HeaderMap) -> Option<String> {
-    headers.get(header::COOKIE).and_then(|x| {
+pub(crate) async fn get_session_from_headers(
+    headers: &axum::http::HeaderMap,
+) -> Option<TokenClaims> {
+    let token = headers.get(header::COOKIE).and_then(|x| {
         x.to_str()
             .unwrap()
             .split("; ")
             .find(|&x| x.starts_with(AUTH_COOKIE))
             .and_then(|x| x.split('=').next_back())
-            .and_then(|x| decode_token(x).map(|jwt| jwt.claims.sub).ok())
-    })
+    })?;
+    session_from_token(token).await
 }
 
-pub fn get_username_from_cookie(header: TypedHeader<Cookie>) -> Option<String> {
-    let token = header.get("token");
+pub async fn get_username_from_cookie(header: TypedHeader<Cookie>) -> Option<String> {
+    let token = header.get("token")?;
+    username_from_token(token).await
+}
 
-    if let Some(token) = token {
-        decode_token(token).map(|jwt| jwt.claims.sub).ok()
-    } else {
-        None
+pub(crate) async fn get_session_from_cookie(header: TypedHeader<Cookie>) -> Option<TokenClaims> {
+    let token = header.get("token")?;
+    session_from_token(token).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderMap;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    fn client_ip(pairs: &[(&'static str, &str)], peer: &str, proxies: &str) -> String {
+        let peer = std::net::SocketAddr::new(peer.parse().unwrap(), 443);
+        ClientInfo::behind(&headers(pairs), peer, &parse_ip_ranges(proxies)).ip
+    }
+
+    #[test]
+    fn trusted_proxies_match_addresses_and_ranges() {
+        let proxies = parse_ip_ranges("10.0.0.0/8, 192.168.1.7, fd00::/16, nonsense, 1.2.3.4/33");
+        assert_eq!(proxies.len(), 3);
+        let trusted = |ip: &str| is_trusted_proxy(&proxies, ip.parse().unwrap());
+        assert!(trusted("10.1.2.3"));
+        assert!(trusted("192.168.1.7"));
+        assert!(trusted("fd00::1"));
+        assert!(trusted("::ffff:10.0.0.1"));
+        assert!(!trusted("11.0.0.1"));
+        assert!(!trusted("192.168.1.8"));
+        assert!(!trusted("fe80::1"));
+        assert!(!is_trusted_proxy(&[], "10.0.0.1".parse().unwrap()));
+        assert!(is_trusted_proxy(
+            &parse_ip_ranges("0.0.0.0/0"),
+            "8.8.8.8".parse().unwrap()
+        ));
+    }
+
+    #[test]
+    fn forwarding_headers_are_ignored_from_untrusted_peers() {
+        let spoofed = [("x-forwarded-for", "1.1.1.1"), ("x-real-ip", "2.2.2.2")];
+        assert_eq!(client_ip(&spoofed, "203.0.113.9", ""), "203.0.113.9");
+        assert_eq!(
+            client_ip(&spoofed, "203.0.113.9", "10.0.0.0/8"),
+            "203.0.113.9"
+        );
+    }
+
+    #[test]
+    fn the_client_is_the_last_untrusted_hop() {
+        // the client prepended a made up address, the proxy appended the real one
+        let forwarded = [("x-forwarded-for", "1.1.1.1, 198.51.100.4, 10.0.0.2")];
+        assert_eq!(
+            client_ip(&forwarded, "10.0.0.1", "10.0.0.0/8"),
+            "198.51.100.4"
+        );
+        let only_proxies = [("x-forwarded-for", "10.0.0.3, 10.0.0.2")];
+        assert_eq!(
+            client_ip(&only_proxies, "10.0.0.1", "10.0.0.0/8"),
+            "10.0.0.3"
+        );
+        let real_ip = [("x-real-ip", "198.51.100.4")];
+        assert_eq!(
+            client_ip(&real_ip, "10.0.0.1", "10.0.0.0/8"),
+            "198.51.100.4"
+        );
+        assert_eq!(client_ip(&[], "10.0.0.1", "10.0.0.0/8"), "10.0.0.1");
     }
 }
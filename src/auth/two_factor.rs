@@ -0,0 +1,284 @@
+//! TOTP second factor (RFC 6238, the 6 digit codes of authenticator apps) and the recovery
+//! codes for when the authenticator is lost. The state lives in [`crate::models::TwoFactor`].
+use crate::models::{MfaChallenge, TwoFactor};
+
+/// The name authenticator apps show next to the account.
+const TOTP_ISSUER: &str = "Realworld Dioxus";
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP: u64 = 30;
+/// Codes of the step before and after the current one still pass, clocks drift.
+const TOTP_SKEW: i64 = 1;
+const RECOVERY_CODES: usize = 10;
+
+/// The name of the cookie holding a login that waits for its second factor.
+pub(crate) static MFA_COOKIE: &str = "mfa_pending";
+/// How long the second factor may be entered after the password, in seconds.
+pub(crate) const MFA_CHALLENGE_TTL: i64 = 5 * 60;
+/// Wrong codes after which the password has to be entered again.
+const MFA_MAX_ATTEMPTS: i64 = 5;
+/// Wrong codes in a row, over every login and the settings, that lock the second factor.
+const MFA_MAX_FAILURES: i64 = 10;
+/// How long a locked second factor refuses codes and new logins, in seconds.
+pub(crate) const MFA_LOCK_SECS: i64 = 15 * 60;
+
+#[derive(Debug)]
+pub(crate) enum SecondFactorError {
+    Invalid,
+    Locked,
+    Database(sqlx::Error),
+}
+
+impl std::fmt::Display for SecondFactorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Invalid => write!(f, "The code is not valid"),
+            Self::Locked => write!(
+                f,
+                "Too many wrong codes, try again in {} minutes",
+                MFA_LOCK_SECS / 60
+            ),
+            Self::Database(x) => write!(f, "{x}"),
+        }
+    }
+}
+
+impl From<sqlx::Error> for SecondFactorError {
+    fn from(err: sqlx::Error) -> Self {
+        Self::Database(err)
+    }
+}
+
+/// A new random secret, base32 encoded the way authenticator apps expect it.
+pub(crate) fn new_totp_secret() -> String {
+    use argon2::password_hash::rand_core::{OsRng, RngCore};
+
+    let mut bytes = vec![0u8; 20];
+    OsRng.fill_bytes(&mut bytes);
+    match totp_rs::Secret::Raw(bytes).to_encoded() {
+        totp_rs::Secret::Encoded(x) => x,
+        totp_rs::Secret::Raw(_) => unreachable!(),
+    }
+}
+
+/// The `otpauth://` link an authenticator app reads from the QR code.
+pub(crate) fn otpauth_url(username: &str, secret: &str) -> String {
+    let issuer = urlencoding::encode(TOTP_ISSUER);
+    format!(
+        "otpauth://totp/{issuer}:{}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={TOTP_DIGITS}&period={TOTP_STEP}",
+        urlencoding::encode(username)
+    )
+}
+
+/// `url` as a QR code, an `<svg>` element to put inline in the page.
+pub(crate) fn qr_svg(url: &str) -> Result<String, String> {
+    let code = qrcode::QrCode::new(url.as_bytes()).map_err(|x| x.to_string())?;
+    let svg = code
+        .render::<qrcode::render::svg::Color>()
+        .min_dimensions(200, 200)
+        .quiet_zone(true)
+        .build();
+    // the renderer writes a whole document, the XML declaration has no place in HTML
+    Ok(match svg.find("<svg") {
+        Some(start) => svg[start..].to_string(),
+        None => svg,
+    })
+}
+
+/// The time step whose code for `secret` is `code`, if any around now.
+fn totp_step(secret: &str, code: &str) -> Option<i64> {
+    let code = code.trim();
+    if code.len() != TOTP_DIGITS || !code.bytes().all(|x| x.is_ascii_digit()) {
+        return None;
+    }
+    let bytes = totp_rs::Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .ok()?;
+    let totp = totp_rs::TOTP::new(totp_rs::Algorithm::SHA1, TOTP_DIGITS, 0, TOTP_STEP, bytes)
+        .map_err(|x| tracing::error!("invalid TOTP secret: {x}"))
+        .ok()?;
+    let now = sqlx::types::chrono::Utc::now().timestamp() / TOTP_STEP as i64;
+    (now - TOTP_SKEW..=now + TOTP_SKEW).find(|step| totp.generate(*step as u64 * TOTP_STEP) == code)
+}
+
+/// Checks a code of the secret being enrolled and turns the second factor on. Returns the
+/// recovery codes to show once, `None` when the code is wrong or there is no enrollment.
+pub(crate) async fn confirm_enrollment(
+    username: &str,
+    code: &str,
+) -> Result<Option<Vec<String>>, sqlx::Error> {
+    let Some(secret) = TwoFactor::pending_secret(username).await? else {
+        return Ok(None);
+    };
+    let Some(step) = totp_step(&secret, code) else {
+        return Ok(None);
+    };
+    let (codes, hashes) = new_recovery_codes();
+    Ok(TwoFactor::enable(username, step, &hashes)
+        .await?
+        .then_some(codes))
+}
+
+/// A current TOTP code or an unused recovery code of `username`, each works only once.
+/// Wrong codes count towards locking the second factor for [`MFA_LOCK_SECS`].
+pub(crate) async fn check_second_factor(
+    username: &str,
+    code: &str,
+) -> Result<(), SecondFactorError> {
+    if TwoFactor::locked(username).await? {
+        return Err(SecondFactorError::Locked);
+    }
+    let Some(secret) = TwoFactor::secret(username).await? else {
+        return Err(SecondFactorError::Invalid);
+    };
+    let valid = match totp_step(&secret, code) {
+        Some(step) => {
+            let mut conn = crate::database::server::get_db().acquire().await?;
+            TwoFactor::use_step(&mut conn, username, step).await?
+        }
+        None => TwoFactor::use_recovery_code(username, &hash_recovery_code(code)).await?,
+    };
+    if !valid {
+        TwoFactor::record_failure(username, MFA_MAX_FAILURES, MFA_LOCK_SECS).await?;
+        return Err(SecondFactorError::Invalid);
+    }
+    TwoFactor::reset_failures(username).await?;
+    Ok(())
+}
+
+/// New recovery codes for `username`, the previous ones stop working.
+pub(crate) async fn regenerate_recovery_codes(username: &str) -> Result<Vec<String>, sqlx::Error> {
+    let (codes, hashes) = new_recovery_codes();
+    TwoFactor::replace_recovery_codes(username, &hashes).await?;
+    Ok(codes)
+}
+
+/// Codes like `3f9a1-07c2e`, returned together with the hashes that get stored.
+fn new_recovery_codes() -> (Vec<String>, Vec<String>) {
+    use argon2::password_hash::rand_core::{OsRng, RngCore};
+
+    (0..RECOVERY_CODES)
+        .map(|_| {
+            // 80 bits, read out as four groups of five hex digits
+            let mut bytes = [0u8; 10];
+            OsRng.fill_bytes(&mut bytes);
+            let hex = super::server::to_hex(&bytes);
+            let code = format!(
+                "{}-{}-{}-{}",
+                &hex[..5],
+                &hex[5..10],
+                &hex[10..15],
+                &hex[15..]
+            );
+            let hash = hash_recovery_code(&code);
+            (code, hash)
+        })
+        .unzip()
+}
+
+/// Case, spaces and dashes do not matter when a recovery code is typed in.
+fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|x| x.is_ascii_alphanumeric())
+        .map(|x| x.to_ascii_lowercase())
+        .collect();
+    super::hash_reset_token(&normalized)
+}
+
+/// Starts a login that still needs the second factor of `username`, the token goes back to
+/// the client in place of a session. Refused while the second factor is locked.
+pub(crate) async fn new_mfa_challenge(username: &str) -> Result<String, SecondFactorError> {
+    if TwoFactor::locked(username).await? {
+        return Err(SecondFactorError::Locked);
+    }
+    let (token, token_hash) = super::new_reset_token();
+    let expires_at = sqlx::types::chrono::Utc::now().timestamp() + MFA_CHALLENGE_TTL;
+    MfaChallenge::insert(&token_hash, username, expires_at).await?;
+    Ok(token)
+}
+
+/// The user of the login `token` stands for when `code` is their second factor. The token
+/// is used up on success and after [`MFA_MAX_ATTEMPTS`] wrong codes.
+pub(crate) async fn complete_mfa_challenge(
+    token: &str,
+    code: &str,
+) -> Result<String, SecondFactorError> {
+    let token_hash = super::hash_reset_token(token);
+    let Some(username) = MfaChallenge::attempt(&token_hash, MFA_MAX_ATTEMPTS).await? else {
+        return Err(SecondFactorError::Invalid);
+    };
+    check_second_factor(&username, code).await?;
+    MfaChallenge::delete(&token_hash).await?;
+    Ok(username)
+}
+
+/// The pending login token of the [`MFA_COOKIE`] cookie.
+pub(crate) fn mfa_token_from_headers(headers: &axum::http::HeaderMap) -> Option<String> {
+    headers
+        .get(axum::http::header::COOKIE)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| {
+            x.split("; ")
+                .find_map(|x| x.strip_prefix(MFA_COOKIE)?.strip_prefix('='))
+        })
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP";
+
+    fn code_at(step: i64) -> String {
+        let bytes = totp_rs::Secret::Encoded(SECRET.to_string())
+            .to_bytes()
+            .unwrap();
+        totp_rs::TOTP::new(totp_rs::Algorithm::SHA1, TOTP_DIGITS, 0, TOTP_STEP, bytes)
+            .unwrap()
+            .generate(step as u64 * TOTP_STEP)
+    }
+
+    #[test]
+    fn totp_step_accepts_codes_around_now() {
+        let now = sqlx::types::chrono::Utc::now().timestamp() / TOTP_STEP as i64;
+        assert_eq!(totp_step(SECRET, &code_at(now)), Some(now));
+        assert_eq!(totp_step(SECRET, &format!(" {} ", code_at(now))), Some(now));
+        assert_eq!(totp_step(SECRET, &code_at(now - 1)), Some(now - 1));
+        assert_eq!(totp_step(SECRET, &code_at(now - 3)), None);
+        assert_eq!(totp_step(SECRET, &code_at(now + 3)), None);
+        assert_eq!(totp_step(SECRET, "12345"), None);
+        assert_eq!(totp_step(SECRET, "12345a"), None);
+    }
+
+    #[test]
+    fn a_time_step_is_only_accepted_once() {
+        use sqlx::Connection;
+
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let mut conn = sqlx::SqliteConnection::connect("sqlite::memory:")
+                    .await
+                    .unwrap();
+                crate::database::server::MIGRATOR
+                    .run(&mut conn)
+                    .await
+                    .unwrap();
+                sqlx::query("UPDATE Users SET totp_secret=$1 WHERE username='user1'")
+                    .bind(SECRET)
+                    .execute(&mut conn)
+                    .await
+                    .unwrap();
+
+                assert!(TwoFactor::use_step(&mut conn, "user1", 100).await.unwrap());
+                // the same code again, or one of an earlier step seen before the last login
+                assert!(!TwoFactor::use_step(&mut conn, "user1", 100).await.unwrap());
+                assert!(!TwoFactor::use_step(&mut conn, "user1", 99).await.unwrap());
+                assert!(TwoFactor::use_step(&mut conn, "user1", 101).await.unwrap());
+                // no second factor, no step to accept
+                assert!(!TwoFactor::use_step(&mut conn, "user2", 100).await.unwrap());
+            });
+    }
+}
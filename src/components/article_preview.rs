@@ -119,6 +119,21 @@ pub fn ArticleMeta(
             if is_preview {
                 AuthorUserIcon { user: article_detail().article.author.clone() }
             }
+            match article_detail().article.status {
+                crate::models::ArticleStatus::Draft => rsx! {
+                    span { class: "bg-gray-200 dark:bg-gray-700 text-gray-700 dark:text-gray-300 px-2 py-1 rounded text-xs",
+                        "Draft"
+                    }
+                },
+                crate::models::ArticleStatus::Scheduled => rsx! {
+                    span { class: "bg-gray-200 dark:bg-gray-700 text-gray-700 dark:text-gray-300 px-2 py-1 rounded text-xs",
+                        "Scheduled for "
+                        {article_detail().article.published_at.unwrap_or_default()}
+                        " UTC"
+                    }
+                },
+                crate::models::ArticleStatus::Published => rsx! {},
+            }
             div { class: "flex items-center gap-1",
 
                 i { class: "fa-solid fa-calendar w-4 h-4" }
@@ -148,6 +163,12 @@ pub fn ArticleMeta(
                     ButtonFav { article_detail }
                 }
             } else {
+                div { class: "",
+                    a { href: "/article/{article_detail().article.slug}/history",
+                        i { class: "fa-solid fa-clock-rotate-left w-4 h-4" }
+                        span { " History" }
+                    }
+                }
                 if is_owner() {
                     div { class: "",
                         a { href: "/editor/{article_detail().article.slug}",
@@ -181,7 +202,7 @@ pub fn ArticleMeta(
 #[tracing::instrument]
 #[post("/api/delete_article", header: TypedHeader<Cookie>)]
 pub async fn delete_article(slug: String) -> Result<bool, ServerFnError> {
-    let Some(logged_user) = crate::auth::get_username_from_cookie(header) else {
+    let Some(logged_user) = crate::auth::get_username_from_cookie(header).await else {
         return Err(ServerFnError::new("you must be logged in"));
     };
 
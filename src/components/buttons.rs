@@ -6,9 +6,10 @@ use dioxus::prelude::*;
 #[tracing::instrument]
 #[post("/api/fav_action", header: TypedHeader<Cookie>)]
 pub async fn fav_action(slug: String) -> Result<bool, ServerFnError> {
-    let Some(username) = crate::auth::get_username_from_cookie(header) else {
+    let Some(username) = crate::auth::get_username_from_cookie(header).await else {
         return Err(ServerFnError::new("You need to be authenticated"));
     };
+    let slug = crate::views::visible_slug(slug, Some(username.clone())).await?;
     toggle_fav(slug, username).await.map_err(|x| {
         tracing::error!("problem while updating the database: {x:?}");
         ServerFnError::new("error while updating the follow")
@@ -16,10 +17,11 @@ pub async fn fav_action(slug: String) -> Result<bool, ServerFnError> {
 }
 
 #[cfg(feature = "server")]
+/// Callers make sure the user can see the article.
 #[tracing::instrument]
-async fn toggle_fav(slug: String, username: String) -> Result<bool, sqlx::Error> {
+pub(crate) async fn toggle_fav(slug: String, username: String) -> Result<bool, sqlx::Error> {
     let db = crate::database::server::get_db();
-    match sqlx::query!(
+    let favorited = match sqlx::query!(
         "SELECT * FROM FavArticles WHERE article=$1 and username=$2",
         slug,
         username
@@ -44,7 +46,17 @@ async fn toggle_fav(slug: String, username: String) -> Result<bool, sqlx::Error>
         .await
         .map(|_| true),
         Err(x) => Err(x),
+    }?;
+
+    let author = crate::models::Article::author_of(&slug).await?;
+    let kind = crate::models::NotificationKind::Favorite;
+    let mut conn = db.acquire().await?;
+    if favorited {
+        crate::models::notify(&mut conn, &author, kind, &username, Some(&slug)).await?;
+    } else {
+        crate::models::retract(&mut conn, &author, kind, &username, Some(&slug)).await?;
     }
+    Ok(favorited)
 }
 
 #[component]
@@ -145,7 +157,7 @@ pub fn ButtonFavFavourited(article_detail: ReadSignal<crate::views::ArticleDetai
 #[tracing::instrument]
 #[post("/api/follow_action", header: TypedHeader<Cookie>)]
 pub async fn follow_action(other_user: String) -> Result<bool, ServerFnError> {
-    let Some(username) = crate::auth::get_username_from_cookie(header) else {
+    let Some(username) = crate::auth::get_username_from_cookie(header).await else {
         return Err(ServerFnError::new("You need to be authenticated"));
     };
     toggle_follow(username, other_user).await.map_err(|x| {
@@ -156,9 +168,9 @@ pub async fn follow_action(other_user: String) -> Result<bool, ServerFnError> {
 
 #[cfg(feature = "server")]
 #[tracing::instrument]
-async fn toggle_follow(current: String, other: String) -> Result<bool, sqlx::Error> {
+pub(crate) async fn toggle_follow(current: String, other: String) -> Result<bool, sqlx::Error> {
     let db = crate::database::server::get_db();
-    match sqlx::query!(
+    let following = match sqlx::query!(
         "SELECT * FROM Follows WHERE follower=$1 and influencer=$2",
         current,
         other
@@ -183,7 +195,16 @@ async fn toggle_follow(current: String, other: String) -> Result<bool, sqlx::Err
         .await
         .map(|_| true),
         Err(x) => Err(x),
+    }?;
+
+    let kind = crate::models::NotificationKind::Follow;
+    let mut conn = db.acquire().await?;
+    if following {
+        crate::models::notify(&mut conn, &other, kind, &current, None).await?;
+    } else {
+        crate::models::retract(&mut conn, &other, kind, &current, None).await?;
     }
+    Ok(following)
 }
 
 #[component]
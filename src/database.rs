@@ -1,13 +1,37 @@
 #[cfg(feature = "server")]
 pub mod server {
+    use std::str::FromStr;
+
+    use sqlx::migrate::Migrate;
+
     static DB: std::sync::OnceLock<sqlx::SqlitePool> = std::sync::OnceLock::new();
 
+    /// The `migrations/` folder, compiled into the binary.
+    pub(crate) static MIGRATOR: sqlx::migrate::Migrator = sqlx::migrate!();
+
+    /// Pool size, `DATABASE_MAX_CONNECTIONS` in the environment.
+    fn max_connections() -> u32 {
+        std::env::var("DATABASE_MAX_CONNECTIONS")
+            .ok()
+            .and_then(|x| x.parse().ok())
+            .filter(|x| *x > 0)
+            .unwrap_or(8)
+    }
+
     async fn create_pool() -> sqlx::SqlitePool {
         let database_url =
             std::env::var("DATABASE_URL").expect("no database url found in ENV vars");
+        // SQLite leaves foreign keys off unless asked, the schema relies on them for cascades
+        let options = sqlx::sqlite::SqliteConnectOptions::from_str(database_url.as_str())
+            .expect("invalid database url")
+            .create_if_missing(true)
+            .foreign_keys(true)
+            .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal)
+            .synchronous(sqlx::sqlite::SqliteSynchronous::Normal)
+            .busy_timeout(std::time::Duration::from_secs(5));
         let pool = sqlx::sqlite::SqlitePoolOptions::new()
-            .max_connections(8)
-            .connect(database_url.as_str())
+            .max_connections(max_connections())
+            .connect_with(options)
             .await
             .expect("could not connect to database");
 
@@ -21,4 +45,49 @@ pub mod server {
     pub fn get_db() -> &'static sqlx::SqlitePool {
         DB.get().expect("database initializing had issues")
     }
+
+    /// Brings the schema up to date when `apply` is set, otherwise only reports pending
+    /// migrations. Fails when the database was migrated by a newer binary.
+    pub async fn migrate(apply: bool) -> Result<(), String> {
+        let known: Vec<i64> = MIGRATOR
+            .iter()
+            .filter(|x| x.migration_type.is_up_migration())
+            .map(|x| x.version)
+            .collect();
+        let latest_known = known.iter().copied().max().unwrap_or_default();
+
+        let applied = {
+            let mut conn = get_db().acquire().await.map_err(|x| x.to_string())?;
+            conn.ensure_migrations_table()
+                .await
+                .map_err(|x| x.to_string())?;
+            conn.list_applied_migrations()
+                .await
+                .map_err(|x| x.to_string())?
+        };
+        let current = applied.iter().map(|x| x.version).max().unwrap_or_default();
+        if current > latest_known {
+            return Err(format!(
+                "database schema version {current} is newer than this binary ({latest_known}), refusing to start"
+            ));
+        }
+
+        let pending = known
+            .iter()
+            .filter(|x| !applied.iter().any(|applied| applied.version == **x))
+            .count();
+        if pending == 0 {
+            tracing::info!("database schema version {current} is up to date");
+        } else if apply {
+            MIGRATOR.run(get_db()).await.map_err(|x| x.to_string())?;
+            tracing::info!(
+                "applied {pending} migration(s), database schema version {current} -> {latest_known}"
+            );
+        } else {
+            tracing::warn!(
+                "database schema version {current}, {pending} migration(s) pending up to {latest_known}, start with --migrate to apply them"
+            );
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,322 @@
+//! RSS 2.0 and Atom feeds of the article streams. RSS is the default, `?format=atom` or an
+//! `Accept` header asking for Atom switches format.
+use axum::{
+    extract::{Path, Query},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use serde::Deserialize;
+use sqlx::types::chrono::{DateTime, NaiveDateTime, Utc};
+
+use crate::models::{Article, User};
+
+/// Number of most recent articles in a feed.
+const FEED_SIZE: i64 = 20;
+
+pub fn router<S>() -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/feed.xml", get(global))
+        .route("/tag/{tag}/feed.xml", get(tag))
+        .route("/profile/{username}/feed.xml", get(profile))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct FeedParams {
+    format: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Rss,
+    Atom,
+}
+
+impl Format {
+    fn new(headers: &HeaderMap, params: &FeedParams) -> Self {
+        let accept = headers
+            .get(header::ACCEPT)
+            .and_then(|x| x.to_str().ok())
+            .unwrap_or_default();
+        match params.format.as_deref() {
+            Some("atom") => Self::Atom,
+            Some(_) => Self::Rss,
+            None if accept.contains("application/atom+xml")
+                && !accept.contains("application/rss+xml") =>
+            {
+                Self::Atom
+            }
+            None => Self::Rss,
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Self::Rss => "application/rss+xml; charset=utf-8",
+            Self::Atom => "application/atom+xml; charset=utf-8",
+        }
+    }
+}
+
+/// What a feed is about, `page` is where the same articles show up in the UI.
+struct Channel {
+    title: String,
+    page: String,
+    feed: String,
+}
+
+async fn global(headers: HeaderMap, Query(params): Query<FeedParams>) -> Response {
+    let articles = Article::for_home_page(0, FEED_SIZE, String::new(), false, None).await;
+    let channel = Channel {
+        title: "Realworld Dioxus".to_string(),
+        page: "/".to_string(),
+        feed: "/feed.xml".to_string(),
+    };
+    respond(&headers, &params, channel, articles).await
+}
+
+async fn tag(
+    headers: HeaderMap,
+    Path(tag): Path<String>,
+    Query(params): Query<FeedParams>,
+) -> Response {
+    let articles = Article::for_home_page(0, FEED_SIZE, tag.clone(), false, None).await;
+    let channel = Channel {
+        title: format!("Realworld Dioxus: #{tag}"),
+        page: "/".to_string(),
+        feed: format!("/tag/{}/feed.xml", urlencoding::encode(&tag)),
+    };
+    respond(&headers, &params, channel, articles).await
+}
+
+async fn profile(
+    headers: HeaderMap,
+    Path(username): Path<String>,
+    Query(params): Query<FeedParams>,
+) -> Response {
+    if User::get(username.clone()).await.is_err() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let articles =
+        Article::for_user_profile_home(username.clone(), false, false, 0, FEED_SIZE, None).await;
+    let channel = Channel {
+        title: format!("Realworld Dioxus: {username}"),
+        page: format!("/profile/{}", urlencoding::encode(&username)),
+        feed: format!("/profile/{}/feed.xml", urlencoding::encode(&username)),
+    };
+    respond(&headers, &params, channel, articles).await
+}
+
+/// Model dates are formatted for the UI and stored in UTC.
+fn parse_date(date: &str) -> DateTime<Utc> {
+    NaiveDateTime::parse_from_str(date, crate::models::DATE_FORMAT)
+        .map(|x| x.and_utc())
+        .unwrap_or_default()
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+async fn respond(
+    headers: &HeaderMap,
+    params: &FeedParams,
+    channel: Channel,
+    articles: Result<Vec<Article>, sqlx::Error>,
+) -> Response {
+    let articles = match articles {
+        Ok(x) => x,
+        Err(err) => {
+            tracing::error!("could not load the feed articles: {}", err);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let slugs = articles.iter().map(|x| x.slug.clone()).collect::<Vec<_>>();
+    let mut bodies = match Article::bodies(&slugs).await {
+        Ok(x) => x,
+        Err(err) => {
+            tracing::error!("could not load the feed article bodies: {}", err);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let entries = articles
+        .into_iter()
+        .map(|article| {
+            let body = bodies.remove(&article.slug).unwrap_or_default();
+            Entry {
+                body_html: crate::markdown::render_article(&body),
+                article,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let format = Format::new(headers, params);
+    let last_modified = entries
+        .iter()
+        .map(|x| parse_date(&x.article.updated_at))
+        .max()
+        .unwrap_or_default();
+    // not the Host header: the answer is cached by shared caches and the ids of the entries
+    // have to stay the same whatever name the reader used
+    let base = crate::public_url();
+    let document = match format {
+        Format::Rss => rss(&base, &channel, &entries, last_modified),
+        Format::Atom => atom(&base, &channel, &entries, last_modified),
+    };
+
+    let etag = {
+        use sha2::{Digest, Sha256};
+        let hash = Sha256::digest(document.as_bytes());
+        format!(
+            "\"{}\"",
+            hash[..16]
+                .iter()
+                .map(|x| format!("{x:02x}"))
+                .collect::<String>()
+        )
+    };
+    let last_modified_header = last_modified
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string();
+
+    // Only the ETag decides: Last-Modified has second precision and goes back in time when
+    // an article leaves the feed, so If-Modified-Since could answer 304 for a changed feed
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|x| x.to_str().ok())
+        .is_some_and(|tags| {
+            tags.split(',')
+                .map(|x| x.trim().trim_start_matches("W/"))
+                .any(|x| x == etag || x == "*")
+        });
+
+    let response_headers = [
+        (header::ETAG, etag),
+        (header::LAST_MODIFIED, last_modified_header),
+        (header::VARY, "Accept".to_string()),
+        (header::CACHE_CONTROL, "public, max-age=300".to_string()),
+    ];
+    if not_modified {
+        return (StatusCode::NOT_MODIFIED, response_headers).into_response();
+    }
+    (
+        response_headers,
+        [(header::CONTENT_TYPE, format.content_type())],
+        document,
+    )
+        .into_response()
+}
+
+struct Entry {
+    article: Article,
+    body_html: String,
+}
+
+impl Entry {
+    fn published_at(&self) -> &str {
+        self.article
+            .published_at
+            .as_deref()
+            .unwrap_or(&self.article.created_at)
+    }
+
+    /// The preview queries leave an empty tag behind for untagged articles.
+    fn tags(&self) -> impl Iterator<Item = &String> {
+        self.article.tag_list.iter().filter(|x| !x.is_empty())
+    }
+}
+
+fn rss(base: &str, channel: &Channel, entries: &[Entry], last_modified: DateTime<Utc>) -> String {
+    let mut xml = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom" xmlns:content="http://purl.org/rss/1.0/modules/content/" xmlns:dc="http://purl.org/dc/elements/1.1/">
+<channel>
+"#,
+    );
+    xml.push_str(&format!(
+        "<title>{}</title>\n<link>{}</link>\n<description>{}</description>\n<atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>\n<lastBuildDate>{}</lastBuildDate>\n",
+        escape(&channel.title),
+        escape(&format!("{base}{}", channel.page)),
+        escape(&channel.title),
+        escape(&format!("{base}{}", channel.feed)),
+        last_modified.to_rfc2822(),
+    ));
+    for entry in entries {
+        let article = &entry.article;
+        let link = escape(&format!(
+            "{base}/article/{}",
+            urlencoding::encode(&article.slug)
+        ));
+        xml.push_str(&format!(
+            "<item>\n<title>{}</title>\n<link>{link}</link>\n<guid isPermaLink=\"true\">{link}</guid>\n<dc:creator>{}</dc:creator>\n<description>{}</description>\n<content:encoded>{}</content:encoded>\n<pubDate>{}</pubDate>\n<atom:updated>{}</atom:updated>\n",
+            escape(&article.title),
+            escape(&article.author.username),
+            escape(&article.description),
+            escape(&entry.body_html),
+            parse_date(entry.published_at()).to_rfc2822(),
+            parse_date(&article.updated_at).to_rfc3339(),
+        ));
+        for tag in entry.tags() {
+            xml.push_str(&format!("<category>{}</category>\n", escape(tag)));
+        }
+        xml.push_str("</item>\n");
+    }
+    xml.push_str("</channel>\n</rss>\n");
+    xml
+}
+
+fn atom(base: &str, channel: &Channel, entries: &[Entry], last_modified: DateTime<Utc>) -> String {
+    let mut xml = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+"#,
+    );
+    let feed_url = escape(&format!("{base}{}?format=atom", channel.feed));
+    xml.push_str(&format!(
+        "<title>{}</title>\n<id>{feed_url}</id>\n<link rel=\"self\" type=\"application/atom+xml\" href=\"{feed_url}\"/>\n<link rel=\"alternate\" type=\"text/html\" href=\"{}\"/>\n<updated>{}</updated>\n",
+        escape(&channel.title),
+        escape(&format!("{base}{}", channel.page)),
+        last_modified.to_rfc3339(),
+    ));
+    for entry in entries {
+        let article = &entry.article;
+        let link = escape(&format!(
+            "{base}/article/{}",
+            urlencoding::encode(&article.slug)
+        ));
+        xml.push_str(&format!(
+            "<entry>\n<title>{}</title>\n<id>{link}</id>\n<link rel=\"alternate\" type=\"text/html\" href=\"{link}\"/>\n<author><name>{}</name><uri>{}</uri></author>\n<published>{}</published>\n<updated>{}</updated>\n<summary>{}</summary>\n<content type=\"html\">{}</content>\n",
+            escape(&article.title),
+            escape(&article.author.username),
+            escape(&format!(
+                "{base}/profile/{}",
+                urlencoding::encode(&article.author.username)
+            )),
+            parse_date(entry.published_at()).to_rfc3339(),
+            parse_date(&article.updated_at).to_rfc3339(),
+            escape(&article.description),
+            escape(&entry.body_html),
+        ));
+        for tag in entry.tags() {
+            xml.push_str(&format!("<category term=\"{}\"/>\n", escape(tag)));
+        }
+        xml.push_str("</entry>\n");
+    }
+    xml.push_str("</feed>\n");
+    xml
+}
@@ -0,0 +1,245 @@
+//! Background work running inside the server process, so nothing depends on an external cron.
+use std::time::Duration;
+
+use crate::models::{Article, DigestRecipient, NotificationGroup, QueuedMail, User};
+
+/// How often scheduled articles are looked at, `PUBLISH_INTERVAL_SECS` in the environment.
+fn publish_interval() -> Duration {
+    Duration::from_secs(
+        std::env::var("PUBLISH_INTERVAL_SECS")
+            .ok()
+            .and_then(|x| x.parse().ok())
+            .filter(|x| *x > 0)
+            .unwrap_or(30),
+    )
+}
+
+/// How often due email digests are looked for, `DIGEST_INTERVAL_SECS` in the environment.
+fn digest_interval() -> Duration {
+    Duration::from_secs(
+        std::env::var("DIGEST_INTERVAL_SECS")
+            .ok()
+            .and_then(|x| x.parse().ok())
+            .filter(|x| *x > 0)
+            .unwrap_or(3600),
+    )
+}
+
+/// How often the mail queue is looked at when nothing new was queued.
+const MAIL_POLL_INTERVAL: Duration = Duration::from_secs(60);
+/// Failed attempts after which a queued mail is given up on.
+const MAIL_MAX_ATTEMPTS: i64 = 10;
+/// The longest wait between two attempts to send the same mail, in seconds.
+const MAIL_MAX_RETRY_DELAY: i64 = 6 * 3600;
+
+/// Starts the background jobs, they live as long as the server.
+pub fn spawn() {
+    tokio::spawn(publish_scheduled());
+    tokio::spawn(send_digests());
+    tokio::spawn(deliver_mail());
+}
+
+/// Publishes scheduled articles once their time passes. The first run happens right away
+/// and catches up with whatever came due while the server was down.
+async fn publish_scheduled() {
+    let mut interval = tokio::time::interval(publish_interval());
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        match Article::publish_due().await {
+            Ok(published) => {
+                for (slug, author) in published {
+                    on_published(&slug, &author).await;
+                }
+            }
+            Err(err) => tracing::error!("could not publish the scheduled articles: {err}"),
+        }
+    }
+}
+
+/// Side effects of a scheduled article going live, the same as for one published from the
+/// editor: the followers of the author are notified. The full-text index, the tag list and
+/// the feeds follow the status on their own.
+async fn on_published(slug: &str, author: &str) {
+    tracing::info!("published scheduled article {slug} of {author}");
+    let notified = match crate::database::server::get_db().acquire().await {
+        Ok(mut conn) => crate::models::notify_followers(&mut conn, author, slug).await,
+        Err(err) => Err(err),
+    };
+    if let Err(err) = notified {
+        tracing::error!("could not notify the followers of {author} about {slug}: {err}");
+    }
+}
+
+/// Emails the users who asked for a daily or weekly digest the notifications they have not
+/// read yet. A digest that could not be queued is tried again on the next run.
+async fn send_digests() {
+    let mut interval = tokio::time::interval(digest_interval());
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        let recipients = match User::due_digests().await {
+            Ok(x) => x,
+            Err(err) => {
+                tracing::error!("could not look for due digests: {err}");
+                continue;
+            }
+        };
+        for recipient in recipients {
+            if let Err(err) = send_digest(&recipient).await {
+                tracing::error!("digest of {} not sent: {err}", recipient.username);
+            }
+        }
+    }
+}
+
+async fn send_digest(recipient: &DigestRecipient) -> Result<(), String> {
+    let until = sqlx::types::chrono::Utc::now().naive_utc();
+    let groups = NotificationGroup::unread_between(&recipient.username, recipient.since, until)
+        .await
+        .map_err(|x| x.to_string())?;
+    // nothing new, nothing to send, the next digest starts from here anyway
+    if !groups.is_empty() {
+        crate::mailer::queue(digest(recipient, &groups)?)
+            .await
+            .map_err(|x| x.to_string())?;
+        tracing::info!("queued the digest of {}", recipient.username);
+    }
+    User::digest_sent(&recipient.username, until)
+        .await
+        .map(|_| ())
+        .map_err(|x| x.to_string())
+}
+
+fn digest(
+    recipient: &DigestRecipient,
+    groups: &[NotificationGroup],
+) -> Result<crate::mailer::Email, String> {
+    let strings = crate::mailer::Strings::load(&recipient.locale)?;
+    let url = crate::public_url();
+    let escape = crate::markdown::html_escape;
+    let (mut items, mut items_html) = (vec![], vec![]);
+    for group in groups {
+        let line = notification_line(&strings, group);
+        match (&group.article, &group.article_title) {
+            (Some(slug), Some(title)) => {
+                items.push(format!("- {line} \"{title}\""));
+                items_html.push(format!(
+                    "<li>{} <a href=\"{url}/article/{}\">{}</a></li>",
+                    escape(&line),
+                    urlencoding::encode(slug),
+                    escape(title)
+                ));
+            }
+            _ => {
+                items.push(format!("- {line}"));
+                items_html.push(format!("<li>{}</li>", escape(&line)));
+            }
+        }
+    }
+    crate::mailer::compose(
+        "digest",
+        &recipient.locale,
+        recipient.email.clone(),
+        &[
+            ("username", recipient.username.clone()),
+            (
+                "since",
+                recipient
+                    .since
+                    .format(crate::models::DATE_FORMAT)
+                    .to_string(),
+            ),
+            ("items", items.join("\n")),
+            ("items_html", items_html.join("\n")),
+            ("notifications_url", format!("{url}/notifications")),
+            ("settings_url", format!("{url}/settings")),
+        ],
+    )
+}
+
+/// "user2 and 3 others favorited" in the recipient's language, the article title goes after.
+fn notification_line(strings: &crate::mailer::Strings, group: &NotificationGroup) -> String {
+    let actors = match group.actors.as_slice() {
+        [] => String::new(),
+        [one] => one.clone(),
+        [one, two] => strings
+            .get("two_actors", &[("one", one.clone()), ("two", two.clone())])
+            .unwrap_or(format!("{one}, {two}")),
+        [one, others @ ..] => strings
+            .get(
+                "many_actors",
+                &[("one", one.clone()), ("others", others.len().to_string())],
+            )
+            .unwrap_or(format!("{one} +{}", others.len())),
+    };
+    let key = group.kind.as_str();
+    let action = match group.actors.len() {
+        0 | 1 => strings.get(key, &[]),
+        _ => strings
+            .get(&format!("{key}.many"), &[])
+            .or_else(|| strings.get(key, &[])),
+    };
+    format!("{actors} {}", action.unwrap_or(key.to_string()))
+}
+
+/// Hands the queued mail to the mailer as soon as it is queued. Failed attempts are retried
+/// later and later, up to [`MAIL_MAX_RETRY_DELAY`] apart, until [`MAIL_MAX_ATTEMPTS`].
+async fn deliver_mail() {
+    loop {
+        if let Err(err) = QueuedMail::delete_expired().await {
+            tracing::error!("could not drop the expired mail: {err}");
+        }
+        let mut stuck = false;
+        'pass: loop {
+            let due = match QueuedMail::due(20).await {
+                Ok(x) if x.is_empty() => break,
+                Ok(x) => x,
+                Err(err) => {
+                    tracing::error!("could not look at the mail queue: {err}");
+                    break;
+                }
+            };
+            for mail in due {
+                if let Err(err) = deliver(mail).await {
+                    // the mail is still due, looking again right away would send it again
+                    tracing::error!("could not update the mail queue: {err}");
+                    stuck = true;
+                    break 'pass;
+                }
+            }
+        }
+        if stuck {
+            tokio::time::sleep(MAIL_POLL_INTERVAL).await;
+        } else {
+            let _ = tokio::time::timeout(MAIL_POLL_INTERVAL, crate::mailer::queued()).await;
+        }
+    }
+}
+
+async fn deliver(mail: QueuedMail) -> Result<(), sqlx::Error> {
+    let email = crate::mailer::Email {
+        to: mail.recipient,
+        subject: mail.subject,
+        text: mail.text_body,
+        html: mail.html_body,
+    };
+    match crate::mailer::send(&email).await {
+        Ok(()) => QueuedMail::delivered(mail.id).await.map(|_| ()),
+        Err(err) if mail.attempts + 1 >= MAIL_MAX_ATTEMPTS => {
+            tracing::error!("gave up on mail {} to {}: {err}", mail.id, email.to);
+            QueuedMail::failed(mail.id, &err, None).await.map(|_| ())
+        }
+        Err(err) => {
+            let retry_in = (60 << mail.attempts.min(16)).min(MAIL_MAX_RETRY_DELAY);
+            tracing::warn!(
+                "mail {} to {} not sent, retrying in {retry_in}s: {err}",
+                mail.id,
+                email.to
+            );
+            QueuedMail::failed(mail.id, &err, Some(retry_in))
+                .await
+                .map(|_| ())
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use std::path::PathBuf;
+
+use super::{Email, Mailer, SendFuture};
+
+/// Writes every mail as an `.eml` file into `MAILER_DIR`, for development and tests.
+pub struct EmlMailer {
+    dir: PathBuf,
+    from: String,
+}
+
+impl EmlMailer {
+    pub fn from_env() -> Result<Self, String> {
+        let dir = PathBuf::from(std::env::var("MAILER_DIR").unwrap_or("mail".to_string()));
+        std::fs::create_dir_all(&dir)
+            .map_err(|x| format!("could not create MAILER_DIR {}: {x}", dir.display()))?;
+        Ok(Self {
+            dir,
+            from: std::env::var("MAILER_EMAIL").unwrap_or("realworld@localhost".to_string()),
+        })
+    }
+}
+
+impl Mailer for EmlMailer {
+    fn send<'a>(&'a self, email: &'a Email) -> SendFuture<'a> {
+        Box::pin(async move {
+            let content = super::message(&self.from, email)
+                .write_to_vec()
+                .map_err(|x| format!("could not write the mail to {}: {x}", email.to))?;
+            // v7 uuids sort by creation, so do the files
+            let path = self.dir.join(format!("{}.eml", uuid::Uuid::now_v7()));
+            tokio::fs::write(&path, content)
+                .await
+                .map_err(|x| format!("could not write {}: {x}", path.display()))
+        })
+    }
+}
@@ -0,0 +1,96 @@
+//! Outgoing email. Mail is put in a queue in the database by [`queue`] and the delivery job
+//! hands it to the backend chosen by `MAILER_BACKEND`, so a mail server that is down delays
+//! the mail without failing the request that wrote it.
+use std::{future::Future, pin::Pin, sync::OnceLock};
+
+use mail_send::mail_builder::MessageBuilder;
+
+mod eml;
+mod log;
+mod smtp;
+mod templates;
+pub use templates::{compose, Strings};
+
+/// An email as the app writes it, the backend adds the sender.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub text: String,
+    /// Sent next to the text when not empty.
+    pub html: String,
+}
+
+pub type SendFuture<'a> = Pin<Box<dyn Future<Output = Result<(), String>> + Send + 'a>>;
+
+/// Where the mail ends up.
+pub trait Mailer: Send + Sync {
+    fn send<'a>(&'a self, email: &'a Email) -> SendFuture<'a>;
+}
+
+static MAILER: OnceLock<Box<dyn Mailer>> = OnceLock::new();
+static QUEUED: tokio::sync::Notify = tokio::sync::Notify::const_new();
+
+/// Sets up the backend named by `MAILER_BACKEND`: `smtp`, `file` for `.eml` files in a
+/// directory or `log`, the default, which only logs the mail.
+pub fn init() -> Result<(), String> {
+    let mailer: Box<dyn Mailer> = match std::env::var("MAILER_BACKEND").as_deref() {
+        Ok("smtp") => Box::new(smtp::SmtpMailer::from_env()?),
+        Ok("file") => Box::new(eml::EmlMailer::from_env()?),
+        Ok("log") | Err(_) => Box::new(log::LogMailer),
+        Ok(x) => return Err(format!("MAILER_BACKEND {x} is not smtp, file or log")),
+    };
+    MAILER
+        .set(mailer)
+        .map_err(|_| "the mailer is already set up".to_string())
+}
+
+/// Puts the mail in the queue and wakes the delivery job up.
+pub async fn queue(email: Email) -> Result<(), sqlx::Error> {
+    insert(email, None).await
+}
+
+/// Same as [`queue`] for mail carrying a link that stops working at `expires_at` (a UTC unix
+/// timestamp): the mail leaves the queue then, so the link does not stay in the database.
+pub async fn queue_until(email: Email, expires_at: i64) -> Result<(), sqlx::Error> {
+    insert(email, Some(expires_at)).await
+}
+
+async fn insert(email: Email, expires_at: Option<i64>) -> Result<(), sqlx::Error> {
+    crate::models::QueuedMail::insert(
+        &email.to,
+        &email.subject,
+        &email.text,
+        &email.html,
+        expires_at,
+    )
+    .await?;
+    QUEUED.notify_one();
+    Ok(())
+}
+
+/// Resolves when something was queued since the last call.
+pub async fn queued() {
+    QUEUED.notified().await
+}
+
+/// Hands the mail to the backend right away, the delivery job is the only caller.
+pub async fn send(email: &Email) -> Result<(), String> {
+    MAILER
+        .get()
+        .ok_or("the mailer is not set up")?
+        .send(email)
+        .await
+}
+
+fn message<'a>(from: &'a str, email: &'a Email) -> MessageBuilder<'a> {
+    let message = MessageBuilder::new()
+        .from(("Realworld Dioxus", from))
+        .to(email.to.as_str())
+        .subject(email.subject.as_str())
+        .text_body(email.text.as_str());
+    match email.html.as_str() {
+        "" => message,
+        html => message.html_body(html),
+    }
+}
@@ -0,0 +1,94 @@
+use std::time::Duration;
+
+use mail_send::SmtpClientBuilder;
+
+use super::{Email, Mailer, SendFuture};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Encryption {
+    /// Plain connection upgraded with STARTTLS, usually on port 587.
+    StartTls,
+    /// TLS from the first byte, usually on port 465.
+    Implicit,
+    /// No encryption at all, only for local test servers.
+    None,
+}
+
+/// Sends through the SMTP server set by the `MAILER_SMTP_*` environment variables.
+#[derive(Debug)]
+pub struct SmtpMailer {
+    server: String,
+    port: u16,
+    encryption: Encryption,
+    from: String,
+    /// Left out when `MAILER_PASSWD` is empty.
+    credentials: Option<(String, String)>,
+}
+
+fn var(name: &str) -> Result<String, String> {
+    std::env::var(name).map_err(|_| format!("{name} is not set"))
+}
+
+impl SmtpMailer {
+    pub fn from_env() -> Result<Self, String> {
+        let encryption = match std::env::var("MAILER_SMTP_TLS").as_deref() {
+            Ok("starttls") | Err(_) => Encryption::StartTls,
+            Ok("implicit") => Encryption::Implicit,
+            Ok("none") => Encryption::None,
+            Ok(x) => {
+                return Err(format!(
+                    "MAILER_SMTP_TLS {x} is not starttls, implicit or none"
+                ))
+            }
+        };
+        let port = match std::env::var("MAILER_SMTP_PORT") {
+            Ok(x) => x
+                .parse()
+                .map_err(|_| format!("MAILER_SMTP_PORT {x} is not a port"))?,
+            Err(_) if encryption == Encryption::Implicit => 465,
+            Err(_) => 587,
+        };
+        let from = var("MAILER_EMAIL")?;
+        let credentials = std::env::var("MAILER_PASSWD")
+            .ok()
+            .filter(|x| !x.is_empty())
+            .map(|x| (from.clone(), x));
+        Ok(Self {
+            server: var("MAILER_SMTP_SERVER")?,
+            port,
+            encryption,
+            from,
+            credentials,
+        })
+    }
+}
+
+impl Mailer for SmtpMailer {
+    fn send<'a>(&'a self, email: &'a Email) -> SendFuture<'a> {
+        Box::pin(async move {
+            let message = super::message(&self.from, email);
+            let mut client = SmtpClientBuilder::new(self.server.as_str(), self.port)
+                .implicit_tls(self.encryption == Encryption::Implicit)
+                .timeout(Duration::from_secs(30));
+            if let Some((user, password)) = &self.credentials {
+                client = client.credentials((user.as_str(), password.as_str()));
+            }
+            let sent = match self.encryption {
+                Encryption::None => match client.connect_plain().await {
+                    Ok(mut x) => x.send(message).await,
+                    Err(x) => Err(x),
+                },
+                Encryption::StartTls | Encryption::Implicit => match client.connect().await {
+                    Ok(mut x) => x.send(message).await,
+                    Err(x) => Err(x),
+                },
+            };
+            sent.map_err(|x| {
+                format!(
+                    "could not send to {} through {}: {x}",
+                    email.to, self.server
+                )
+            })
+        })
+    }
+}
@@ -0,0 +1,210 @@
+//! Templates of the mail the app sends. Every mail has a `<name>.subject`, a `<name>.txt` and
+//! a `<name>.html` template per language under `templates/email/<locale>/`, built into the
+//! binary. A file with the same path under `MAILER_TEMPLATES` takes precedence, and a
+//! language without its own template falls back to [`DEFAULT_LOCALE`].
+use std::collections::HashMap;
+
+use super::Email;
+
+/// The language every template exists in.
+pub const DEFAULT_LOCALE: &str = "en";
+
+macro_rules! builtin {
+    ($($path:literal),* $(,)?) => {
+        &[$(($path, include_str!(concat!("../../templates/email/", $path)))),*]
+    };
+}
+
+static BUILTIN: &[(&str, &str)] = builtin!(
+    "en/reset_password.subject",
+    "en/reset_password.txt",
+    "en/reset_password.html",
+    "en/digest.subject",
+    "en/digest.txt",
+    "en/digest.html",
+    "en/strings.txt",
+    "en/verify_email.subject",
+    "en/verify_email.txt",
+    "en/verify_email.html",
+    "fr/reset_password.subject",
+    "fr/reset_password.txt",
+    "fr/reset_password.html",
+    "fr/digest.subject",
+    "fr/digest.txt",
+    "fr/digest.html",
+    "fr/strings.txt",
+    "fr/verify_email.subject",
+    "fr/verify_email.txt",
+    "fr/verify_email.html",
+);
+
+/// Every version of `file` there is for `locale`, the one to use first.
+fn versions(locale: &str, file: &str) -> Result<Vec<String>, String> {
+    let overrides = std::env::var("MAILER_TEMPLATES")
+        .ok()
+        .map(std::path::PathBuf::from);
+    // the locale comes from the database, it must not walk out of the template directory
+    let locale = match crate::models::LOCALES.iter().find(|(x, _)| *x == locale) {
+        Some((x, _)) => *x,
+        None => DEFAULT_LOCALE,
+    };
+    let mut locales = vec![locale];
+    if locale != DEFAULT_LOCALE {
+        locales.push(DEFAULT_LOCALE);
+    }
+
+    let mut versions = vec![];
+    for locale in locales {
+        let path = format!("{locale}/{file}");
+        if let Some(dir) = &overrides {
+            match std::fs::read_to_string(dir.join(&path)) {
+                Ok(x) => versions.push(x),
+                Err(x) if x.kind() == std::io::ErrorKind::NotFound => {}
+                Err(x) => return Err(format!("could not read the template {path}: {x}")),
+            }
+        }
+        if let Some((_, x)) = BUILTIN.iter().find(|(x, _)| *x == path) {
+            versions.push(x.to_string());
+        }
+    }
+    Ok(versions)
+}
+
+fn load(locale: &str, file: &str) -> Result<String, String> {
+    versions(locale, file)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("no template {file}"))
+}
+
+/// Replaces every `{{name}}` with the value of `name`. For HTML the values are escaped, apart
+/// from the ones whose name ends with `_html`.
+fn render(template: &str, vars: &[(&str, String)], html: bool) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        rendered.push_str(&rest[..start]);
+        let name = rest[start + 2..start + end].trim();
+        match vars.iter().find(|(x, _)| *x == name) {
+            Some((_, value)) if html && !name.ends_with("_html") => {
+                rendered.push_str(&crate::markdown::html_escape(value))
+            }
+            Some((_, value)) => rendered.push_str(value),
+            None => tracing::warn!("the mail template has no value for {name}"),
+        }
+        rest = &rest[start + end + 2..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// Writes the `name` mail to `to` in the language `locale`.
+pub fn compose(
+    name: &str,
+    locale: &str,
+    to: String,
+    vars: &[(&str, String)],
+) -> Result<Email, String> {
+    Ok(Email {
+        to,
+        subject: render(
+            load(locale, &format!("{name}.subject"))?.trim(),
+            vars,
+            false,
+        ),
+        text: render(&load(locale, &format!("{name}.txt"))?, vars, false),
+        html: render(&load(locale, &format!("{name}.html"))?, vars, true),
+    })
+}
+
+/// Short translated texts from the `strings.txt` files, `key = value` lines. A key missing
+/// from a language is looked up in [`DEFAULT_LOCALE`].
+pub struct Strings(Vec<HashMap<String, String>>);
+
+impl Strings {
+    pub fn load(locale: &str) -> Result<Self, String> {
+        let catalogs = versions(locale, "strings.txt")?
+            .iter()
+            .map(|catalog| {
+                catalog
+                    .lines()
+                    .filter(|x| !x.trim_start().starts_with('#'))
+                    .filter_map(|x| x.split_once('='))
+                    .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+                    .collect()
+            })
+            .collect();
+        Ok(Self(catalogs))
+    }
+
+    pub fn get(&self, key: &str, vars: &[(&str, String)]) -> Option<String> {
+        self.0
+            .iter()
+            .find_map(|x| x.get(key))
+            .map(|x| render(x, vars, false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_escapes_values_in_html() {
+        let vars = [
+            ("name", "<b>Tom & Jerry</b>".to_string()),
+            ("list_html", "<ul></ul>".to_string()),
+        ];
+        assert_eq!(
+            render("Hi {{name}}, {{ list_html }}", &vars, true),
+            "Hi &lt;b&gt;Tom &amp; Jerry&lt;/b&gt;, <ul></ul>"
+        );
+        assert_eq!(render("Hi {{name}}", &vars, false), "Hi <b>Tom & Jerry</b>");
+    }
+
+    #[test]
+    fn render_leaves_out_unknown_names() {
+        assert_eq!(render("a {{missing}} b", &[], true), "a  b");
+        assert_eq!(render("a {{open", &[], true), "a {{open");
+    }
+
+    #[test]
+    fn templates_fall_back_to_the_default_locale() {
+        let english = load(DEFAULT_LOCALE, "reset_password.subject").unwrap();
+        let french = load("fr", "reset_password.subject").unwrap();
+        assert_ne!(english, french);
+        assert_eq!(load("de", "reset_password.subject").unwrap(), english);
+        // a locale is never used as a path
+        assert_eq!(load("../en", "reset_password.subject").unwrap(), english);
+        assert!(load("fr", "missing.txt").is_err());
+    }
+
+    #[test]
+    fn strings_fall_back_to_the_default_locale() {
+        let french = Strings::load("fr").unwrap();
+        let vars = [("one", "<a>".to_string()), ("two", "b".to_string())];
+        assert_eq!(french.get("two_actors", &vars).as_deref(), Some("<a> et b"));
+        assert_eq!(french.get("missing", &vars), None);
+        let catalogs = Strings(vec![
+            HashMap::from([("follow".to_string(), "vous suit".to_string())]),
+            HashMap::from([
+                ("follow".to_string(), "follows you".to_string()),
+                ("new_key".to_string(), "only in {{one}}".to_string()),
+            ]),
+        ]);
+        assert_eq!(catalogs.get("follow", &[]).as_deref(), Some("vous suit"));
+        assert_eq!(
+            catalogs.get("new_key", &vars).as_deref(),
+            Some("only in <a>")
+        );
+        let english = Strings::load(DEFAULT_LOCALE).unwrap();
+        assert_eq!(english.get("follow.many", &[]), None);
+        assert_eq!(
+            french.get("follow.many", &[]).as_deref(),
+            Some("vous suivent désormais")
+        );
+    }
+}
@@ -2,18 +2,34 @@ use dioxus::prelude::*;
 
 use dioxus::router::root_router;
 use views::{Home, Profile};
-use views::{Login, ResetPasswd, Settings, SignUp};
+use views::{Login, ResetPasswd, Settings, SignUp, VerifyEmail};
 
 use crate::models::{Pagination, User};
 use crate::views::Article;
+use crate::views::ArticleHistory;
 use crate::views::Editor;
+use crate::views::Notifications;
 
 mod views;
 
+#[cfg(feature = "server")]
+mod admin;
+#[cfg(feature = "server")]
+mod api;
 mod auth;
 mod components;
 mod database;
+#[cfg(feature = "server")]
+mod feed;
+#[cfg(feature = "server")]
+mod jobs;
+#[cfg(feature = "server")]
+mod mailer;
+#[cfg(feature = "server")]
+mod markdown;
 mod models;
+#[cfg(feature = "server")]
+mod slug;
 
 #[derive(Routable, Clone)]
 #[rustfmt::skip]
@@ -23,11 +39,15 @@ enum Route {
         Home {},
         #[route("/profile/:profile_user")]
         Profile{profile_user: String},
+        #[route("/notifications")]
+        Notifications {},
         #[end_layout]
         #[route("/newarticle")]
         NewArticle {},
         #[route("/article/:slug")]
         Article {slug: String },
+        #[route("/article/:slug/history")]
+        ArticleHistory {slug: String },
         #[route("/editor/:slug")]
         Editor{slug: String},
         #[route("/login")]
@@ -44,6 +64,10 @@ enum Route {
         ResetPasswd{
             token: String
         },
+        #[route("/verify_email?:token")]
+        VerifyEmail{
+            token: String
+        },
 
 }
 
@@ -66,18 +90,58 @@ fn main() {
                 use tracing::Level;
 
                 dioxus_logger::init(Level::INFO).expect("failed to init logger");
-                launch_server(App).await;
+
+                // `--migrate` and `--no-migrate` may go anywhere, the last one wins. Migrations
+                // run by default, `--no-migrate` leaves the schema alone.
+                let mut migrate = true;
+                let mut args = vec![];
+                for arg in std::env::args().skip(1) {
+                    match arg.as_str() {
+                        "--migrate" => migrate = true,
+                        "--no-migrate" => migrate = false,
+                        _ => args.push(arg),
+                    }
+                }
+                init_database(migrate).await;
+
+                if args.first().is_some_and(|x| x == "admin") {
+                    if let Err(err) = admin::run(&args[1..]).await {
+                        eprintln!("{err}");
+                        std::process::exit(1);
+                    }
+                } else {
+                    launch_server(App).await;
+                }
             });
     }
 }
 
+/// Where the app is reachable from the outside, `PUBLIC_URL` in the environment. Links that
+/// leave the app, in emails and feeds, start with it rather than with the `Host` a client sent.
 #[cfg(feature = "server")]
-async fn launch_server(_component: fn() -> Element) {
-    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+pub(crate) fn public_url() -> String {
+    std::env::var("PUBLIC_URL")
+        .map(|x| x.trim_end_matches('/').to_string())
+        .unwrap_or("http://localhost:8080".to_string())
+}
 
+#[cfg(feature = "server")]
+async fn init_database(migrate: bool) {
     database::server::init_db()
         .await
         .expect("Problem during initialization of the database");
+    database::server::migrate(migrate)
+        .await
+        .expect("Problem while migrating the database");
+}
+
+#[cfg(feature = "server")]
+async fn launch_server(_component: fn() -> Element) {
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+    auth::init_keys().expect("Problem while loading the JWT keys");
+    mailer::init().expect("Problem while setting up the mailer");
+    jobs::spawn();
 
     let ip =
         dioxus_cli_config::server_ip().unwrap_or_else(|| IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)));
@@ -85,10 +149,13 @@ async fn launch_server(_component: fn() -> Element) {
     let address = SocketAddr::new(ip, port);
     let listener = tokio::net::TcpListener::bind(address).await.unwrap();
     let router = axum::Router::new()
+        .merge(api::router())
+        .merge(feed::router())
         .serve_dioxus_application(ServeConfig::new(), App)
         // .serve_dioxus_application(ServeConfig::new().unwrap(), App)
         .layer(axum::middleware::from_fn(crate::auth::auth_middleware))
-        .into_make_service();
+        .layer(axum::middleware::from_fn(crate::slug::redirect_old_slugs))
+        .into_make_service_with_connect_info::<SocketAddr>();
 
     axum::serve(listener, router).await.unwrap();
 }
@@ -114,6 +181,10 @@ struct PageAmount(i64);
 #[derive(Clone, Default)]
 struct ThemeMode(String);
 
+/// Unread notifications of the logged user, shown on the `NavBar`.
+#[derive(Clone, Default)]
+struct UnreadNotifications(i64);
+
 #[component]
 fn App() -> Element {
     // Build cool things ✌️
@@ -129,6 +200,7 @@ fn App() -> Element {
     use_context_provider(|| Signal::new(SearchString(String::new())));
     use_context_provider(|| Signal::new(PageAmount(10)));
     use_context_provider(|| Signal::new(ThemeMode(String::from("dark"))));
+    use_context_provider(|| Signal::new(UnreadNotifications(0)));
 
     use_effect(move || {
         let mut search_meta = use_context::<Signal<SearchMeta>>();
@@ -207,6 +279,18 @@ fn App() -> Element {
             crossorigin: "anonymous",
             referrerpolicy: "no-referrer",
         }
+        document::Link {
+            rel: "alternate",
+            r#type: "application/rss+xml",
+            title: "Realworld Dioxus",
+            href: "/feed.xml",
+        }
+        document::Link {
+            rel: "alternate",
+            r#type: "application/atom+xml",
+            title: "Realworld Dioxus (Atom)",
+            href: "/feed.xml?format=atom",
+        }
         document::Title { "My App" }
         div { Router::<Route> {} }
     }
@@ -225,8 +309,21 @@ fn Footer() -> Element {
 #[component]
 fn NavBar() -> Element {
     let mut logged_user = use_context::<Signal<LoggedInUser>>();
+    let mut unread = use_context::<Signal<UnreadNotifications>>();
     let nav = navigator();
 
+    // looked up again on every page change
+    let path = use_route::<Route>().to_string();
+    use_resource(use_reactive!(|(path,)| async move {
+        let _ = path;
+        let count = if logged_user().0.is_some() {
+            views::unread_notifications().await.unwrap_or_default()
+        } else {
+            0
+        };
+        unread.set(UnreadNotifications(count));
+    }));
+
     let on_submit = move |evt: FormEvent| async move {
         evt.prevent_default();
         match auth::logout().await {
@@ -284,6 +381,17 @@ fn NavBar() -> Element {
                                 }
                             }
                         }
+                        Link { to: Route::Notifications {},
+                            div { class: "group navitem relative",
+                                i { class: "fa-solid fa-bell navitem-icon" }
+                                if unread().0 > 0 {
+                                    span { class: "notification-badge", {unread().0.to_string()} }
+                                }
+                                span { class: "text-xs md:text-base mt-1 font-semibold",
+                                    "Notifications"
+                                }
+                            }
+                        }
                         Link { to: Route::Settings {},
                             div { class: "group navitem",
                                 i { class: "fa-solid fa-gear navitem-icon" }
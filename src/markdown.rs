@@ -0,0 +1,225 @@
+//! Markdown rendering for user written content. Everything goes through a strict
+//! HTML sanitizer afterwards, so the output is safe for `dangerous_inner_html`.
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag, TagEnd};
+
+static ARTICLE_SANITIZER: std::sync::OnceLock<ammonia::Builder<'static>> =
+    std::sync::OnceLock::new();
+
+fn article_sanitizer() -> &'static ammonia::Builder<'static> {
+    ARTICLE_SANITIZER.get_or_init(|| {
+        let mut builder = ammonia::Builder::default();
+        builder
+            // task list items are rendered as disabled checkboxes
+            .add_tags(&["input"])
+            .add_tag_attributes("input", &["type", "checked", "disabled"])
+            // fenced code keeps its `language-xxx` class
+            .add_tag_attributes("code", &["class"])
+            .attribute_filter(|element, attribute, value| match (element, attribute) {
+                ("input", "type") if value != "checkbox" => None,
+                ("code", "class") if !value.starts_with("language-") => None,
+                _ => Some(Cow::Borrowed(value)),
+            })
+            .link_rel(Some("noopener noreferrer nofollow"));
+        builder
+    })
+}
+
+static COMMENT_SANITIZER: std::sync::OnceLock<ammonia::Builder<'static>> =
+    std::sync::OnceLock::new();
+
+/// Comments only keep paragraphs, emphasis, code and links.
+fn comment_sanitizer() -> &'static ammonia::Builder<'static> {
+    COMMENT_SANITIZER.get_or_init(|| {
+        let mut builder = ammonia::Builder::default();
+        builder
+            .tags(HashSet::from([
+                "p", "br", "em", "strong", "code", "pre", "a",
+            ]))
+            .link_rel(Some("noopener noreferrer nofollow"));
+        builder
+    })
+}
+
+/// Renders an article body (CommonMark plus tables, fenced code and task lists) to
+/// sanitized HTML.
+pub fn render_article(body: &str) -> String {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_TASKLISTS);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+
+    let mut unsafe_html = String::with_capacity(body.len() * 3 / 2);
+    html::push_html(&mut unsafe_html, Parser::new_ext(body, options));
+    article_sanitizer().clean(&unsafe_html).to_string()
+}
+
+/// `@username` mentions in a piece of text with their byte range, `@` included. An `@` right
+/// after a word character is part of an email address, and a trailing `.` or `-` ends the
+/// sentence rather than the name.
+fn text_mentions(text: &str) -> impl Iterator<Item = (std::ops::Range<usize>, &str)> {
+    static MENTION: std::sync::OnceLock<regex::Regex> = std::sync::OnceLock::new();
+    MENTION
+        .get_or_init(|| regex::Regex::new(r"@[\w][\w.\-]*").unwrap())
+        .find_iter(text)
+        .filter(|x| {
+            !text[..x.start()]
+                .chars()
+                .next_back()
+                .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '@')
+        })
+        .map(|x| {
+            let name = x.as_str()[1..].trim_end_matches(['.', '-']);
+            (x.start()..x.start() + 1 + name.len(), name)
+        })
+}
+
+/// The names mentioned in a comment, outside of code, without duplicates.
+pub fn mentions(body: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut in_code_block = false;
+    for event in Parser::new(body) {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(TagEnd::CodeBlock) => in_code_block = false,
+            Event::Text(text) if !in_code_block => {
+                for (_, name) in text_mentions(&text) {
+                    if !names.iter().any(|x| x == name) {
+                        names.push(name.to_string());
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    names
+}
+
+/// Splits a text on the mentions of `users`, which become links to their profiles.
+fn link_mentions<'a>(text: CowStr<'a>, users: &HashSet<String>) -> Vec<Event<'a>> {
+    let mut events = Vec::new();
+    let mut last = 0;
+    for (range, name) in text_mentions(&text) {
+        if !users.contains(name) {
+            continue;
+        }
+        if range.start > last {
+            events.push(Event::Text(text[last..range.start].to_string().into()));
+        }
+        events.push(Event::InlineHtml(
+            format!(
+                r#"<a href="/profile/{}">@{}</a>"#,
+                urlencoding::encode(name),
+                html_escape(name)
+            )
+            .into(),
+        ));
+        last = range.end;
+    }
+    if last == 0 {
+        return vec![Event::Text(text)];
+    }
+    if last < text.len() {
+        events.push(Event::Text(text[last..].to_string().into()));
+    }
+    events
+}
+
+pub(crate) fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Renders a comment to sanitized HTML with a small subset of Markdown: emphasis, inline
+/// code, code blocks and links. Mentions of `users` link to their profiles, HTML written in
+/// the comment is shown as text and every line break is kept.
+pub fn render_comment(body: &str, users: &HashSet<String>) -> String {
+    let mut in_code_block = false;
+    let events = Parser::new(body).flat_map(|event| match event {
+        Event::Start(Tag::CodeBlock(_)) => {
+            in_code_block = true;
+            vec![event]
+        }
+        Event::End(TagEnd::CodeBlock) => {
+            in_code_block = false;
+            vec![event]
+        }
+        Event::Text(text) if !in_code_block => link_mentions(text, users),
+        Event::Html(text) | Event::InlineHtml(text) => vec![Event::Text(text)],
+        Event::SoftBreak => vec![Event::HardBreak],
+        _ => vec![event],
+    });
+
+    let mut unsafe_html = String::with_capacity(body.len() * 3 / 2);
+    html::push_html(&mut unsafe_html, events);
+    comment_sanitizer().clean(&unsafe_html).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn users() -> HashSet<String> {
+        HashSet::from(["alice".to_string(), "bob".to_string()])
+    }
+
+    #[test]
+    fn render_comment_shows_raw_html_as_text() {
+        assert_eq!(
+            render_comment("<b>bold</b> *em*", &users()),
+            "<p>&lt;b&gt;bold&lt;/b&gt; <em>em</em></p>\n"
+        );
+        let html = render_comment("<script>alert(1)</script>", &users());
+        assert!(!html.contains("<script"), "{html}");
+        let html = render_comment("<img src=x onerror=alert(1)>", &users());
+        assert!(!html.contains("<img"), "{html}");
+    }
+
+    #[test]
+    fn render_comment_keeps_safe_links_only() {
+        assert_eq!(
+            render_comment("[x](javascript:alert(1)) [y](https://e.com)", &users()),
+            "<p><a rel=\"noopener noreferrer nofollow\">x</a> \
+            <a href=\"https://e.com\" rel=\"noopener noreferrer nofollow\">y</a></p>\n"
+        );
+    }
+
+    #[test]
+    fn render_comment_keeps_line_breaks() {
+        assert_eq!(render_comment("a\nb", &users()), "<p>a<br>\nb</p>\n");
+    }
+
+    #[test]
+    fn render_comment_links_known_users_outside_code() {
+        assert_eq!(
+            render_comment("hi @alice and @carol.", &users()),
+            "<p>hi <a href=\"/profile/alice\" rel=\"noopener noreferrer nofollow\">@alice</a> \
+            and @carol.</p>\n"
+        );
+        assert_eq!(
+            render_comment("`@alice`\n```\n@bob\n```", &users()),
+            "<p><code>@alice</code></p>\n<pre><code>@bob\n</code></pre>\n"
+        );
+    }
+
+    #[test]
+    fn mentions_skip_code_emails_and_html() {
+        assert_eq!(mentions("hi @alice and @carol."), ["alice", "carol"]);
+        assert_eq!(mentions("@bob, @bob and @alice-"), ["bob", "alice"]);
+        assert_eq!(mentions("`@alice`\n```\n@bob\n```"), Vec::<String>::new());
+        assert_eq!(mentions("mail a@alice.com or @@bob"), Vec::<String>::new());
+        // a line starting with HTML is an HTML block, shown as text without links, inline
+        // HTML is shown as text around the links
+        assert_eq!(mentions("<div>@alice</div>"), Vec::<String>::new());
+        assert_eq!(mentions("<b>@alice</b> hi"), ["alice"]);
+        assert_eq!(
+            render_comment("<b>@alice</b> hi", &users()),
+            "<p>&lt;b&gt;<a href=\"/profile/alice\" rel=\"noopener noreferrer nofollow\">@alice</a>\
+            &lt;/b&gt; hi</p>\n"
+        );
+    }
+}
@@ -1,8 +1,36 @@
 use super::UserPreview;
 use serde::{Deserialize, Serialize};
 
-#[cfg(feature = "server")]
-use dioxus::fullstack::{Cookie, TypedHeader};
+/// Drafts and scheduled articles are only visible to their author, listings, feeds and
+/// search skip them. A scheduled article gets published once its `published_at` passes.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, Debug, PartialEq)]
+pub enum ArticleStatus {
+    Draft,
+    Scheduled,
+    #[default]
+    Published,
+}
+
+impl ArticleStatus {
+    #[cfg(feature = "server")]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Draft => "draft",
+            Self::Scheduled => "scheduled",
+            Self::Published => "published",
+        }
+    }
+}
+
+impl From<&str> for ArticleStatus {
+    fn from(status: &str) -> Self {
+        match status {
+            "draft" => Self::Draft,
+            "scheduled" => Self::Scheduled,
+            _ => Self::Published,
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
 pub struct Article {
@@ -10,13 +38,25 @@ pub struct Article {
     pub title: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub body: Option<String>,
+    /// `body` rendered from markdown to sanitized HTML, only filled for the article page.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body_html: Option<String>,
     pub description: String,
     pub created_at: String,
+    pub updated_at: String,
+    /// When the article went live, or is going to for scheduled ones. `None` for drafts.
+    #[serde(default)]
+    pub published_at: Option<String>,
     pub favorites_count: i64,
     pub tag_list: Vec<String>,
     pub author: UserPreview,
     pub fav: bool,
     pub comments_count: i64,
+    #[serde(default)]
+    pub status: ArticleStatus,
+    /// Bumped on every save, see `update_article`.
+    #[serde(default)]
+    pub version: i64,
 }
 
 impl Article {
@@ -27,9 +67,8 @@ impl Article {
         amount: i64,
         tag: String,
         my_feed: bool,
-        header: TypedHeader<Cookie>,
+        username: Option<String>,
     ) -> Result<Vec<Self>, sqlx::Error> {
-        let username = crate::auth::get_username_from_cookie(header);
         let offset = page * amount;
         sqlx::query!(
             "
@@ -38,8 +77,11 @@ SELECT
     a.title,
     a.description,
     a.created_at,
+    a.updated_at,
+    a.published_at,
+    a.version,
     (SELECT COUNT(*) FROM FavArticles WHERE article=a.slug) as favorites_count,
-    (SELECT COUNT(*) FROM comments WHERE article=a.slug) as comments_count,
+    (SELECT COUNT(*) FROM comments WHERE article=a.slug AND deleted=0) as comments_count,
     u.username, u.image,
     EXISTS(SELECT 1 FROM FavArticles WHERE article=a.slug and username=$5) as fav,
     EXISTS(SELECT 1 FROM Follows WHERE follower=$5 and influencer=u.username) as following,
@@ -47,6 +89,8 @@ SELECT
 FROM Articles as a
     JOIN Users as u ON a.author = u.username
 WHERE
+    a.status = 'published'
+    AND
     CASE WHEN $3!='' THEN a.slug in (SELECT distinct article FROM ArticleTags WHERE tag=$3)
     ELSE 1=1
     END
@@ -54,7 +98,7 @@ WHERE
     CASE WHEN $4 THEN u.username in (SELECT influencer FROM Follows WHERE follower=$5)
     ELSE 1=1
     END
-ORDER BY a.created_at desc
+ORDER BY a.published_at desc
 LIMIT $1 OFFSET $2",
             amount,
             offset,
@@ -66,10 +110,15 @@ LIMIT $1 OFFSET $2",
             slug: x.slug,
             title: x.title,
             body: None, // no need
+            body_html: None,
             fav: x.fav != 0,
             // fav: x.fav.unwrap_or_default(),
             description: x.description,
             created_at: x.created_at.format(super::DATE_FORMAT).to_string(),
+            updated_at: x.updated_at.format(super::DATE_FORMAT).to_string(),
+            published_at: x
+                .published_at
+                .map(|x| x.format(super::DATE_FORMAT).to_string()),
             favorites_count: x.favorites_count,
             // favorites_count: x.favorites_count.unwrap_or_default(),
             author: UserPreview {
@@ -86,6 +135,8 @@ LIMIT $1 OFFSET $2",
                 .collect::<Vec<String>>(),
             comments_count: x.comments_count,
             // comments_count: x.comments_count.unwrap_or_default(),
+            status: ArticleStatus::Published,
+            version: x.version,
         })
         .fetch_all(crate::database::server::get_db())
         .await
@@ -96,11 +147,11 @@ LIMIT $1 OFFSET $2",
     pub async fn for_user_profile_home(
         username: String,
         favourites: bool,
+        drafts: bool,
         page: i64,
         amount: i64,
-        header: TypedHeader<Cookie>,
+        logged_user: Option<String>,
     ) -> Result<Vec<Self>, sqlx::Error> {
-        let logged_user = crate::auth::get_username_from_cookie(header);
         let offset = page * amount;
         sqlx::query!(
                 "
@@ -109,35 +160,47 @@ LIMIT $1 OFFSET $2",
         a.title,
         a.description,
         a.created_at,
+        a.updated_at,
+        a.published_at,
+        a.status,
+        a.version,
         u.username,
         u.image,
         (SELECT COUNT(*) FROM FavArticles WHERE article=a.slug) as favorites_count,
-        (SELECT COUNT(*) FROM comments WHERE article=a.slug) as comments_count,
+        (SELECT COUNT(*) FROM comments WHERE article=a.slug AND deleted=0) as comments_count,
         EXISTS(SELECT 1 FROM FavArticles WHERE article=a.slug and username=$2) as fav,
         EXISTS(SELECT 1 FROM Follows WHERE follower=$2 and influencer=a.author) as following,
         (SELECT string_agg(tag, ' ') FROM ArticleTags WHERE article = a.slug) as tag_list
     FROM Articles as a
         JOIN Users as u ON u.username = a.author
     WHERE
-        CASE WHEN $3 THEN
+        CASE WHEN $6 THEN a.author = $1 AND a.author = $2 AND a.status != 'published'
+        WHEN $3 THEN
             EXISTS(SELECT fa.article, fa.username FROM FavArticles as fa WHERE fa.article=a.slug AND fa.username=$1)
-        ELSE a.author = $1
+            AND a.status = 'published'
+        ELSE a.author = $1 AND a.status = 'published'
         END
-        ORDER BY a.created_at desc
+        ORDER BY CASE WHEN $6 THEN a.updated_at ELSE a.published_at END desc
         LIMIT $4 OFFSET $5",
                 username,
                 logged_user,
                 favourites,
                 amount,
                 offset,
+                drafts,
             )
             .map(|x| Self {
                 slug: x.slug,
                 title: x.title,
                 body: None, // no need
+                body_html: None,
                 fav: x.fav != 0,
                 description: x.description,
                 created_at: x.created_at.format(super::DATE_FORMAT).to_string(),
+                updated_at: x.updated_at.format(super::DATE_FORMAT).to_string(),
+                published_at: x
+                    .published_at
+                    .map(|x| x.format(super::DATE_FORMAT).to_string()),
                 favorites_count: x.favorites_count,
                 tag_list: x
                     .tag_list
@@ -149,17 +212,84 @@ LIMIT $1 OFFSET $2",
                     following: x.following !=0,
                 },
                 comments_count: x.comments_count,
+                status: ArticleStatus::from(x.status.as_str()),
+                version: x.version,
             })
             .fetch_all(crate::database::server::get_db())
             .await
     }
 
+    /// Bodies of the listed articles, the listings above leave them out.
     #[cfg(feature = "server")]
-    pub async fn for_article(
-        slug: String,
-        header: TypedHeader<Cookie>,
-    ) -> Result<Self, sqlx::Error> {
-        let username = crate::auth::get_username_from_cookie(header);
+    pub async fn bodies(
+        slugs: &[String],
+    ) -> Result<std::collections::HashMap<String, String>, sqlx::Error> {
+        let slugs = serde_json::to_string(slugs).unwrap_or_default();
+        sqlx::query!(
+            "SELECT slug, body FROM Articles WHERE slug IN (SELECT value FROM json_each($1))",
+            slugs
+        )
+        .map(|x| (x.slug, x.body))
+        .fetch_all(crate::database::server::get_db())
+        .await
+        .map(|x| x.into_iter().collect())
+    }
+
+    #[cfg(feature = "server")]
+    pub async fn count_for_home_page(
+        tag: String,
+        my_feed: bool,
+        username: Option<String>,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar!(
+            "
+SELECT COUNT(*)
+FROM Articles as a
+WHERE
+    a.status = 'published'
+    AND
+    CASE WHEN $1!='' THEN a.slug in (SELECT distinct article FROM ArticleTags WHERE tag=$1)
+    ELSE 1=1
+    END
+    AND
+    CASE WHEN $2 THEN a.author in (SELECT influencer FROM Follows WHERE follower=$3)
+    ELSE 1=1
+    END",
+            tag,
+            my_feed,
+            username,
+        )
+        .fetch_one(crate::database::server::get_db())
+        .await
+    }
+
+    #[cfg(feature = "server")]
+    pub async fn count_for_user_profile_home(
+        username: String,
+        favourites: bool,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar!(
+            "
+SELECT COUNT(*)
+FROM Articles as a
+WHERE
+    a.status = 'published'
+    AND
+    CASE WHEN $2 THEN
+        EXISTS(SELECT 1 FROM FavArticles as fa WHERE fa.article=a.slug AND fa.username=$1)
+    ELSE a.author = $1
+    END",
+            username,
+            favourites,
+        )
+        .fetch_one(crate::database::server::get_db())
+        .await
+    }
+
+    /// An unpublished article is only found by its author. Old slugs find the article under
+    /// its current one.
+    #[cfg(feature = "server")]
+    pub async fn for_article(slug: String, username: Option<String>) -> Result<Self, sqlx::Error> {
         sqlx::query!(
                 r#"
         SELECT
@@ -168,16 +298,21 @@ LIMIT $1 OFFSET $2",
             a.body as body,
             a.description as description,
             a.created_at as created_at,
+            a.updated_at as updated_at,
+            a.published_at as published_at,
+            a.status as status,
+            a.version as version,
             (SELECT string_agg(tag, ' ') FROM ArticleTags WHERE article = a.slug) as "tag_list: Option<String>",
             (SELECT COUNT(*) FROM FavArticles WHERE article = a.slug) as "fav_count: Option<i64>",
-            (SELECT COUNT(*) FROM comments WHERE article = a.slug) as "comments_count: Option<i64>",
+            (SELECT COUNT(*) FROM comments WHERE article = a.slug AND deleted = 0) as "comments_count: Option<i64>",
             u.username as username,
             u.image as image,
             EXISTS(SELECT 1 FROM FavArticles WHERE article=a.slug and username=$2) as "fav: Option<i64>",
             EXISTS(SELECT 1 FROM Follows WHERE follower=$2 and influencer=a.author) as "following: Option<i64>"
         FROM Articles a
             JOIN Users u ON a.author = u.username
-        WHERE slug = $1
+        WHERE slug = coalesce((SELECT article FROM ArticleSlugs WHERE old_slug = $1), $1)
+            AND (a.status = 'published' OR a.author = $2)
         "#,
                 slug,
                 username,
@@ -186,6 +321,7 @@ LIMIT $1 OFFSET $2",
                 slug: x.slug,
                 title: x.title,
                 description: x.description,
+                body_html: Some(crate::markdown::render_article(&x.body)),
                 body: Some(x.body),
                 tag_list: x
                     .tag_list
@@ -197,6 +333,10 @@ LIMIT $1 OFFSET $2",
                     .collect::<Vec<_>>(),
                 favorites_count: x.fav_count.flatten().unwrap_or_default(),
                 created_at: x.created_at.format(super::DATE_FORMAT).to_string(),
+                updated_at: x.updated_at.format(super::DATE_FORMAT).to_string(),
+                published_at: x
+                    .published_at
+                    .map(|x| x.format(super::DATE_FORMAT).to_string()),
                 fav: x.fav.flatten().unwrap_or_default() != 0,
                 author: UserPreview {
                     username: x.username,
@@ -204,11 +344,65 @@ LIMIT $1 OFFSET $2",
                     following: x.following.flatten().unwrap_or_default() != 0,
                 },
                 comments_count: x.comments_count.flatten().unwrap_or_default(),
+                status: ArticleStatus::from(x.status.as_str()),
+                version: x.version,
             })
             .fetch_one(crate::database::server::get_db())
             .await
     }
 
+    /// Publishes the scheduled articles whose time has come, returning their slugs and
+    /// authors.
+    #[cfg(feature = "server")]
+    pub async fn publish_due() -> Result<Vec<(String, String)>, sqlx::Error> {
+        sqlx::query!(
+            "UPDATE Articles SET status = 'published'
+            WHERE status = 'scheduled' AND published_at <= CURRENT_TIMESTAMP
+            RETURNING slug, author"
+        )
+        .map(|x| (x.slug, x.author))
+        .fetch_all(crate::database::server::get_db())
+        .await
+    }
+
+    /// Whether nothing but the article `current` uses `slug`, as its slug or as an old one.
+    #[cfg(feature = "server")]
+    pub async fn slug_available(
+        conn: &mut sqlx::SqliteConnection,
+        slug: &str,
+        current: &str,
+    ) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"SELECT
+                NOT EXISTS(SELECT 1 FROM Articles WHERE slug=$1 AND slug!=$2)
+                AND NOT EXISTS(SELECT 1 FROM ArticleSlugs WHERE old_slug=$1 AND article!=$2)
+                as "available!: bool""#,
+            slug,
+            current
+        )
+        .fetch_one(conn)
+        .await
+    }
+
+    /// The current slug of an article that was renamed away from `old_slug`.
+    #[cfg(feature = "server")]
+    pub async fn renamed_to(old_slug: &str) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar!(
+            "SELECT article FROM ArticleSlugs WHERE old_slug=$1",
+            old_slug
+        )
+        .fetch_optional(crate::database::server::get_db())
+        .await
+    }
+
+    /// Author of an article whatever its status, for maintenance tasks.
+    #[cfg(feature = "server")]
+    pub async fn author_of(slug: &str) -> Result<String, sqlx::Error> {
+        sqlx::query_scalar!("SELECT author FROM Articles WHERE slug=$1", slug)
+            .fetch_one(crate::database::server::get_db())
+            .await
+    }
+
     #[cfg(feature = "server")]
     pub async fn delete(
         slug: String,
@@ -4,56 +4,336 @@ pub struct Comment {
     pub article: String,
     pub username: String,
     pub body: String,
+    /// The body rendered with mentions linked, left out for deleted comments.
+    #[serde(default)]
+    pub body_html: Option<String>,
     pub created_at: String,
     pub user_image: Option<String>,
+    /// The comment this one replies to.
+    #[serde(default)]
+    pub parent: Option<i64>,
+    /// Deleted while it had replies, only kept so the thread stays in place. Its body is
+    /// left out.
+    #[serde(default)]
+    pub deleted: bool,
+    #[serde(default)]
+    pub edited_at: Option<String>,
+    /// Still within the window in which its author can edit it.
+    #[serde(default)]
+    pub editable: bool,
+}
+
+/// A body a comment had before an edit replaced it.
+#[cfg(feature = "server")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommentEdit {
+    pub body: String,
+    /// When the edit replaced it.
+    pub replaced_at: String,
+}
+
+/// How long after posting a comment can be edited, `COMMENT_EDIT_MINUTES` in the environment,
+/// 15 minutes by default and 0 to turn editing off.
+#[cfg(feature = "server")]
+fn edit_window_secs() -> i64 {
+    static EDIT_WINDOW: std::sync::OnceLock<i64> = std::sync::OnceLock::new();
+    *EDIT_WINDOW.get_or_init(|| {
+        std::env::var("COMMENT_EDIT_MINUTES")
+            .ok()
+            .and_then(|x| x.parse::<i64>().ok())
+            .filter(|x| *x >= 0)
+            .unwrap_or(15)
+            * 60
+    })
+}
+
+/// The `datetime()` modifier giving the oldest creation date still open for edits.
+#[cfg(feature = "server")]
+fn edit_window_start() -> String {
+    format!("-{} seconds", edit_window_secs())
+}
+
+/// The users among `names`.
+#[cfg(feature = "server")]
+async fn existing_users(
+    names: &[String],
+) -> Result<std::collections::HashSet<String>, sqlx::Error> {
+    if names.is_empty() {
+        return Ok(Default::default());
+    }
+    let names = serde_json::to_string(names).unwrap_or_default();
+    sqlx::query_scalar!(
+        "SELECT username FROM Users WHERE username IN (SELECT value FROM json_each($1))",
+        names
+    )
+    .fetch_all(crate::database::server::get_db())
+    .await
+    .map(|x| x.into_iter().collect())
+}
+
+/// Records the users a comment mentions, apart from its author, and returns the ones that
+/// were not mentioned in it before. Mentions from before an edit are kept, those users were
+/// told already.
+#[cfg(feature = "server")]
+async fn record_mentions(
+    conn: &mut sqlx::SqliteConnection,
+    comment: i64,
+    author: &str,
+    body: &str,
+) -> Result<Vec<String>, sqlx::Error> {
+    let names = crate::markdown::mentions(body);
+    if names.is_empty() {
+        return Ok(vec![]);
+    }
+    let names = serde_json::to_string(&names).unwrap_or_default();
+    sqlx::query_scalar!(
+        "INSERT OR IGNORE INTO CommentMentions(comment, username)
+        SELECT $1, username FROM Users
+        WHERE username IN (SELECT value FROM json_each($2)) AND username != $3
+        RETURNING username",
+        comment,
+        names,
+        author
+    )
+    .fetch_all(conn)
+    .await
+}
+
+/// Tells the author of the parent comment about a reply, the author of the article about a
+/// new comment and the mentioned users about their mention, each of them once.
+#[cfg(feature = "server")]
+async fn notify_comment(
+    conn: &mut sqlx::SqliteConnection,
+    article: &str,
+    author: &str,
+    parent: Option<i64>,
+    mentioned: Vec<String>,
+) -> Result<(), sqlx::Error> {
+    use crate::models::{notify, NotificationKind};
+
+    let mut told = vec![author.to_string()];
+    if let Some(parent) = parent {
+        let recipient = sqlx::query_scalar!("SELECT username FROM Comments WHERE id=$1", parent)
+            .fetch_one(&mut *conn)
+            .await?;
+        notify(
+            &mut *conn,
+            &recipient,
+            NotificationKind::Reply,
+            author,
+            Some(article),
+        )
+        .await?;
+        told.push(recipient);
+    }
+    let article_author = sqlx::query_scalar!("SELECT author FROM Articles WHERE slug=$1", article)
+        .fetch_one(&mut *conn)
+        .await?;
+    if !told.contains(&article_author) {
+        notify(
+            &mut *conn,
+            &article_author,
+            NotificationKind::Comment,
+            author,
+            Some(article),
+        )
+        .await?;
+        told.push(article_author);
+    }
+    for name in mentioned.iter().filter(|x| !told.contains(x)) {
+        notify(
+            &mut *conn,
+            name,
+            NotificationKind::Mention,
+            author,
+            Some(article),
+        )
+        .await?;
+    }
+    Ok(())
 }
 
 impl Comment {
+    /// Replying needs a `parent` that belongs to the same article and is not deleted,
+    /// otherwise nothing is inserted.
     #[cfg(feature = "server")]
     pub async fn insert(
         article: String,
         username: String,
         body: String,
+        parent: Option<i64>,
     ) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
-        sqlx::query!(
-            "INSERT INTO Comments(article, username, body) VALUES ($1, $2, $3)",
+        let mut transaction = crate::database::server::get_db().begin().await?;
+        let inserted = sqlx::query!(
+            "INSERT INTO Comments(article, username, body, parent)
+            SELECT $1, $2, $3, $4
+            WHERE $4 IS NULL
+                OR EXISTS (SELECT 1 FROM Comments WHERE id=$4 AND article=$1 AND deleted=0)",
             article,
             username,
-            body
+            body,
+            parent
         )
-        .execute(crate::database::server::get_db())
-        .await
+        .execute(transaction.as_mut())
+        .await?;
+        if inserted.rows_affected() > 0 {
+            let mentioned = record_mentions(
+                transaction.as_mut(),
+                inserted.last_insert_rowid(),
+                &username,
+                &body,
+            )
+            .await?;
+            notify_comment(transaction.as_mut(), &article, &username, parent, mentioned).await?;
+        }
+        transaction.commit().await?;
+        Ok(inserted)
     }
 
     #[cfg(feature = "server")]
     pub async fn get_all(article: String) -> Result<Vec<Self>, sqlx::Error> {
-        sqlx::query!(
-            "
-        SELECT c.*, u.image FROM Comments as c
+        let window_start = edit_window_start();
+        let mut comments = sqlx::query!(
+            r#"
+        SELECT c.*, u.image, c.created_at > datetime('now', $2) as "editable!: bool"
+            FROM Comments as c
             JOIN Users as u ON u.username=c.username
         WHERE c.article=$1
-        ORDER BY c.created_at",
-            article
+        ORDER BY c.created_at"#,
+            article,
+            window_start
         )
         .map(|x| Self {
             id: x.id,
             article: x.article,
             username: x.username,
-            body: x.body,
+            body: if x.deleted { String::new() } else { x.body },
+            body_html: None,
             created_at: x.created_at.format(super::DATE_FORMAT).to_string(),
             user_image: x.image,
+            parent: x.parent,
+            deleted: x.deleted,
+            edited_at: x
+                .edited_at
+                .map(|x| x.format(super::DATE_FORMAT).to_string()),
+            editable: x.editable && !x.deleted,
+        })
+        .fetch_all(crate::database::server::get_db())
+        .await?;
+
+        let mut names = comments
+            .iter()
+            .flat_map(|x| crate::markdown::mentions(&x.body))
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        let users = existing_users(&names).await?;
+        for comment in comments.iter_mut().filter(|x| !x.deleted) {
+            comment.body_html = Some(crate::markdown::render_comment(&comment.body, &users));
+        }
+        Ok(comments)
+    }
+
+    /// Replaces the body when `user` wrote the comment and the edit window is still open,
+    /// the previous body goes to the edit history. Returns whether the comment was changed.
+    #[cfg(feature = "server")]
+    pub async fn update(id: i64, user: String, body: String) -> Result<bool, sqlx::Error> {
+        let window_start = edit_window_start();
+        let mut transaction = crate::database::server::get_db().begin().await?;
+        let kept = sqlx::query!(
+            "INSERT INTO CommentEdits(comment, body)
+            SELECT id, body FROM Comments
+            WHERE id=$1 and username=$2 and deleted=0 and created_at > datetime('now', $3)",
+            id,
+            user,
+            window_start
+        )
+        .execute(transaction.as_mut())
+        .await?
+        .rows_affected();
+        if kept == 0 {
+            return Ok(false);
+        }
+        sqlx::query!(
+            "UPDATE Comments SET body=$2, edited_at=CURRENT_TIMESTAMP WHERE id=$1",
+            id,
+            body
+        )
+        .execute(transaction.as_mut())
+        .await?;
+        let article = sqlx::query_scalar!("SELECT article FROM Comments WHERE id=$1", id)
+            .fetch_one(transaction.as_mut())
+            .await?;
+        for name in record_mentions(transaction.as_mut(), id, &user, &body).await? {
+            crate::models::notify(
+                transaction.as_mut(),
+                &name,
+                crate::models::NotificationKind::Mention,
+                &user,
+                Some(&article),
+            )
+            .await?;
+        }
+        transaction.commit().await?;
+        Ok(true)
+    }
+
+    /// The bodies a comment had before its edits, oldest first.
+    #[cfg(feature = "server")]
+    pub async fn edits(id: i64) -> Result<Vec<CommentEdit>, sqlx::Error> {
+        sqlx::query!(
+            "SELECT body, created_at FROM CommentEdits WHERE comment=$1 ORDER BY id",
+            id
+        )
+        .map(|x| CommentEdit {
+            body: x.body,
+            replaced_at: x.created_at.format(super::DATE_FORMAT).to_string(),
         })
         .fetch_all(crate::database::server::get_db())
         .await
     }
 
+    /// A comment with replies becomes a placeholder, its body stays for the moderators but is
+    /// not shown anymore. Others are removed together with the placeholders above them that
+    /// have nothing left to hold.
     #[cfg(feature = "server")]
-    pub async fn delete(
-        id: i32,
-        user: String,
-    ) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
-        sqlx::query!("DELETE FROM Comments WHERE id=$1 and username=$2", id, user)
-            .execute(crate::database::server::get_db())
-            .await
+    pub async fn delete(id: i32, user: String) -> Result<(), sqlx::Error> {
+        let mut transaction = crate::database::server::get_db().begin().await?;
+        let placeholder = sqlx::query!(
+            "UPDATE Comments SET deleted=1
+            WHERE id=$1 and username=$2 and deleted=0
+                and EXISTS (SELECT 1 FROM Comments as r WHERE r.parent=Comments.id)",
+            id,
+            user
+        )
+        .execute(transaction.as_mut())
+        .await?
+        .rows_affected();
+        if placeholder == 0 {
+            let mut parent = sqlx::query!(
+                "DELETE FROM Comments
+                WHERE id=$1 and username=$2 and deleted=0
+                    and NOT EXISTS (SELECT 1 FROM Comments as r WHERE r.parent=Comments.id)
+                RETURNING parent",
+                id,
+                user
+            )
+            .fetch_optional(transaction.as_mut())
+            .await?
+            .and_then(|x| x.parent);
+            while let Some(id) = parent {
+                parent = sqlx::query!(
+                    "DELETE FROM Comments
+                    WHERE id=$1 and deleted=1
+                        and NOT EXISTS (SELECT 1 FROM Comments as r WHERE r.parent=Comments.id)
+                    RETURNING parent",
+                    id
+                )
+                .fetch_optional(transaction.as_mut())
+                .await?
+                .and_then(|x| x.parent);
+            }
+        }
+        transaction.commit().await
     }
 }
@@ -0,0 +1,88 @@
+/// An emailed link confirming an address, the current one of a user or the one they want
+/// instead. Only the hash of the token is stored, like for [`super::PasswordReset`].
+pub struct EmailVerification;
+
+impl EmailVerification {
+    /// `expires_at` is a UTC unix timestamp.
+    pub async fn insert(
+        token_hash: &str,
+        username: &str,
+        email: &str,
+        expires_at: i64,
+    ) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO EmailVerifications(token_hash, username, email, expires_at)
+            VALUES ($1, $2, $3, datetime($4, 'unixepoch'))",
+            token_hash,
+            username,
+            email,
+            expires_at
+        )
+        .execute(crate::database::server::get_db())
+        .await
+    }
+
+    /// Whether a link was sent to the user less than a minute ago.
+    pub async fn sent_recently(username: &str) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"SELECT EXISTS (
+                SELECT 1 FROM EmailVerifications
+                WHERE username=$1 and created_at > datetime('now', '-1 minute')
+            ) as "sent!: bool""#,
+            username
+        )
+        .fetch_one(crate::database::server::get_db())
+        .await
+    }
+
+    /// How many links were sent to the user in the last hour.
+    pub async fn sent_last_hour(username: &str) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"SELECT count(*) as "count!: i64" FROM EmailVerifications
+            WHERE username=$1 and created_at > datetime('now', '-1 hour')"#,
+            username
+        )
+        .fetch_one(crate::database::server::get_db())
+        .await
+    }
+
+    /// Marks the address of a still usable token confirmed, replacing the current one when it
+    /// was the pending one, and returns its user. A token for an address the user moved away
+    /// from in the meantime does nothing.
+    pub async fn confirm(token_hash: &str) -> Result<Option<String>, sqlx::Error> {
+        let mut transaction = crate::database::server::get_db().begin().await?;
+        let Some(link) = sqlx::query!(
+            "SELECT username, email FROM EmailVerifications
+            WHERE token_hash=$1 and consumed_at IS NULL and expires_at > CURRENT_TIMESTAMP",
+            token_hash
+        )
+        .fetch_optional(transaction.as_mut())
+        .await?
+        else {
+            return Ok(None);
+        };
+        let confirmed = sqlx::query!(
+            "UPDATE Users SET email=$2, pending_email=NULL, email_verified=1
+            WHERE username=$1 and (email=$2 or pending_email=$2)",
+            link.username,
+            link.email
+        )
+        .execute(transaction.as_mut())
+        .await?
+        .rows_affected();
+        if confirmed == 0 {
+            return Ok(None);
+        }
+        // the other links went to addresses that are either confirmed or abandoned now, they
+        // stay around to keep counting for the limits on how often links are sent
+        sqlx::query!(
+            "UPDATE EmailVerifications SET consumed_at=CURRENT_TIMESTAMP
+            WHERE username=$1 and consumed_at IS NULL",
+            link.username
+        )
+        .execute(transaction.as_mut())
+        .await?;
+        transaction.commit().await?;
+        Ok(Some(link.username))
+    }
+}
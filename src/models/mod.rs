@@ -1,15 +1,50 @@
 mod user;
-pub use user::{User, UserPreview};
+#[cfg(feature = "server")]
+pub use user::DigestRecipient;
+pub use user::{User, UserPreview, EMAIL_DIGESTS, LOCALES};
 mod pagination;
 pub use pagination::Pagination;
 pub mod article;
 
-pub use article::Article;
+pub use article::{Article, ArticleStatus};
+
+mod revision;
+pub use revision::{DiffLine, Revision};
 
 mod comment;
 pub use comment::Comment;
 
+mod notification;
+#[cfg(feature = "server")]
+pub(crate) use notification::{notify, notify_followers, retract};
+pub use notification::{NotificationGroup, NotificationKind};
+
+mod session;
+pub use session::Session;
+
+#[cfg(feature = "server")]
+mod password_reset;
+#[cfg(feature = "server")]
+pub use password_reset::PasswordReset;
+
+#[cfg(feature = "server")]
+mod email_verification;
+#[cfg(feature = "server")]
+pub use email_verification::EmailVerification;
+
+#[cfg(feature = "server")]
+mod two_factor;
+#[cfg(feature = "server")]
+pub use two_factor::{MfaChallenge, TwoFactor};
+
 #[cfg(feature = "server")]
-const DATE_FORMAT: &str = "%d/%m/%Y %H:%M";
+mod queued_mail;
+#[cfg(feature = "server")]
+pub use queued_mail::QueuedMail;
+
+#[cfg(feature = "server")]
+pub(crate) const DATE_FORMAT: &str = "%d/%m/%Y %H:%M";
 mod search;
-pub use search::MatchedArticles;
+#[cfg(feature = "server")]
+pub use search::{optimize_search_index, rebuild_search_index};
+pub use search::{HighlightSegment, MatchedArticles};
@@ -0,0 +1,273 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum NotificationKind {
+    #[default]
+    Follow,
+    Favorite,
+    Comment,
+    Reply,
+    Mention,
+    Publish,
+}
+
+impl NotificationKind {
+    #[cfg(feature = "server")]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Follow => "follow",
+            Self::Favorite => "favorite",
+            Self::Comment => "comment",
+            Self::Reply => "reply",
+            Self::Mention => "mention",
+            Self::Publish => "publish",
+        }
+    }
+}
+
+impl From<&str> for NotificationKind {
+    fn from(kind: &str) -> Self {
+        match kind {
+            "favorite" => Self::Favorite,
+            "comment" => Self::Comment,
+            "reply" => Self::Reply,
+            "mention" => Self::Mention,
+            "publish" => Self::Publish,
+            _ => Self::Follow,
+        }
+    }
+}
+
+/// Notifications of the same kind about the same article, shown as one line.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct NotificationGroup {
+    pub ids: Vec<i64>,
+    pub kind: NotificationKind,
+    pub article: Option<String>,
+    pub article_title: Option<String>,
+    /// Newest first, each one once.
+    pub actors: Vec<String>,
+    /// When the newest one happened.
+    pub created_at: String,
+    pub unread: bool,
+}
+
+impl NotificationGroup {
+    /// "user2 and 3 others favorited", the article title goes after it.
+    pub fn text(&self) -> String {
+        let actors = match self.actors.as_slice() {
+            [] => String::new(),
+            [one] => one.clone(),
+            [one, two] => format!("{one} and {two}"),
+            [one, others @ ..] => format!("{one} and {} others", others.len()),
+        };
+        let action = match self.kind {
+            NotificationKind::Follow => "started following you",
+            NotificationKind::Favorite => "favorited",
+            NotificationKind::Comment => "commented on",
+            NotificationKind::Reply => "replied to your comment on",
+            NotificationKind::Mention => "mentioned you in",
+            NotificationKind::Publish => "published",
+        };
+        format!("{actors} {action}")
+    }
+}
+
+/// How many notifications the notifications page groups, older ones are not shown.
+#[cfg(feature = "server")]
+const LIST_LIMIT: i64 = 200;
+
+/// Tells `recipient` that `actor` did something, nobody is told about their own doing. An
+/// unread notification of the same event is replaced, so toggling a favorite back and forth
+/// does not pile them up.
+#[cfg(feature = "server")]
+pub async fn notify(
+    conn: &mut sqlx::SqliteConnection,
+    recipient: &str,
+    kind: NotificationKind,
+    actor: &str,
+    article: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    if recipient == actor {
+        return Ok(());
+    }
+    retract(&mut *conn, recipient, kind, actor, article).await?;
+    let kind = kind.as_str();
+    sqlx::query!(
+        "INSERT INTO Notifications(username, kind, actor, article) VALUES ($1, $2, $3, $4)",
+        recipient,
+        kind,
+        actor,
+        article
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Tells the followers of `author` that their article `slug` went live, once per article:
+/// an unread notification from an earlier publication of it is replaced.
+#[cfg(feature = "server")]
+pub async fn notify_followers(
+    conn: &mut sqlx::SqliteConnection,
+    author: &str,
+    slug: &str,
+) -> Result<(), sqlx::Error> {
+    let kind = NotificationKind::Publish.as_str();
+    sqlx::query!(
+        "DELETE FROM Notifications
+        WHERE kind=$1 AND actor=$2 AND article=$3 AND read_at IS NULL",
+        kind,
+        author,
+        slug
+    )
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query!(
+        "INSERT INTO Notifications(username, kind, actor, article)
+        SELECT follower, $1, $2, $3 FROM Follows WHERE influencer=$2 AND follower!=$2",
+        kind,
+        author,
+        slug
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Takes back an unread notification when its event is undone, like an unfollow.
+#[cfg(feature = "server")]
+pub async fn retract(
+    conn: &mut sqlx::SqliteConnection,
+    recipient: &str,
+    kind: NotificationKind,
+    actor: &str,
+    article: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    let kind = kind.as_str();
+    sqlx::query!(
+        "DELETE FROM Notifications
+        WHERE username=$1 AND kind=$2 AND actor=$3 AND article IS $4 AND read_at IS NULL",
+        recipient,
+        kind,
+        actor,
+        article
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+#[cfg(feature = "server")]
+struct NotificationRow {
+    id: i64,
+    kind: String,
+    actor: String,
+    article: Option<String>,
+    title: Option<String>,
+    created_at: sqlx::types::chrono::NaiveDateTime,
+    unread: bool,
+}
+
+#[cfg(feature = "server")]
+impl NotificationGroup {
+    /// The latest notifications of a user, newest first. Unread and read ones are grouped
+    /// apart.
+    pub async fn for_user(username: &str) -> Result<Vec<Self>, sqlx::Error> {
+        let rows = sqlx::query_as!(
+            NotificationRow,
+            r#"SELECT n.id as "id!", n.kind, n.actor, n.article, a.title as "title?",
+                n.created_at, n.read_at IS NULL as "unread!: bool"
+            FROM Notifications as n
+                LEFT JOIN Articles as a ON a.slug=n.article
+            WHERE n.username=$1
+            ORDER BY n.created_at DESC, n.id DESC
+            LIMIT $2"#,
+            username,
+            LIST_LIMIT
+        )
+        .fetch_all(crate::database::server::get_db())
+        .await?;
+        Ok(Self::group(rows))
+    }
+
+    /// The unread notifications of a user from after `since` up to `until`, for the digest.
+    pub async fn unread_between(
+        username: &str,
+        since: sqlx::types::chrono::NaiveDateTime,
+        until: sqlx::types::chrono::NaiveDateTime,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let rows = sqlx::query_as!(
+            NotificationRow,
+            r#"SELECT n.id as "id!", n.kind, n.actor, n.article, a.title as "title?",
+                n.created_at, n.read_at IS NULL as "unread!: bool"
+            FROM Notifications as n
+                LEFT JOIN Articles as a ON a.slug=n.article
+            WHERE n.username=$1 AND n.read_at IS NULL AND n.created_at > $2 AND n.created_at <= $3
+            ORDER BY n.created_at DESC, n.id DESC"#,
+            username,
+            since,
+            until
+        )
+        .fetch_all(crate::database::server::get_db())
+        .await?;
+        Ok(Self::group(rows))
+    }
+
+    /// Groups rows that come newest first, by kind, article and whether they were read.
+    fn group(rows: Vec<NotificationRow>) -> Vec<Self> {
+        let mut groups: Vec<Self> = Vec::new();
+        let mut index = std::collections::HashMap::new();
+        for row in rows {
+            let kind = NotificationKind::from(row.kind.as_str());
+            let key = (kind, row.article.clone(), row.unread);
+            let group = match index.get(&key) {
+                Some(&i) => &mut groups[i],
+                None => {
+                    index.insert(key, groups.len());
+                    groups.push(Self {
+                        kind,
+                        article: row.article,
+                        article_title: row.title,
+                        created_at: row.created_at.format(super::DATE_FORMAT).to_string(),
+                        unread: row.unread,
+                        ..Default::default()
+                    });
+                    groups.last_mut().unwrap()
+                }
+            };
+            group.ids.push(row.id);
+            if !group.actors.contains(&row.actor) {
+                group.actors.push(row.actor);
+            }
+        }
+        groups
+    }
+
+    pub async fn unread_count(username: &str) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"SELECT count(*) as "count!: i64" FROM Notifications
+            WHERE username=$1 AND read_at IS NULL"#,
+            username
+        )
+        .fetch_one(crate::database::server::get_db())
+        .await
+    }
+
+    /// Marks the given notifications of `username` read, all of them when `ids` is `None`.
+    pub async fn mark_read(
+        username: &str,
+        ids: Option<&[i64]>,
+    ) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        let ids = ids.map(|x| serde_json::to_string(x).unwrap_or_default());
+        sqlx::query!(
+            "UPDATE Notifications SET read_at=CURRENT_TIMESTAMP
+            WHERE username=$1 AND read_at IS NULL
+                AND ($2 IS NULL OR id IN (SELECT value FROM json_each($2)))",
+            username,
+            ids
+        )
+        .execute(crate::database::server::get_db())
+        .await
+    }
+}
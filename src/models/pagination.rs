@@ -5,6 +5,7 @@ pub struct Pagination {
     page: Option<i64>,
     amount: Option<i64>,
     favourites: Option<bool>,
+    drafts: Option<bool>,
 }
 
 impl Pagination {
@@ -21,6 +22,10 @@ impl Pagination {
         self.favourites.unwrap_or_default()
     }
     #[inline]
+    pub fn get_drafts(&self) -> bool {
+        self.drafts.unwrap_or_default()
+    }
+    #[inline]
     pub fn get_page(&self) -> i64 {
         self.page.unwrap_or_default()
     }
@@ -53,6 +58,12 @@ impl Pagination {
         self
     }
 
+    #[inline]
+    pub fn set_drafts(mut self, drafts: bool) -> Self {
+        self.drafts = Some(drafts);
+        self
+    }
+
     #[inline]
     pub fn reset_page(mut self) -> Self {
         self.page = Some(0);
@@ -81,6 +92,7 @@ impl Default for Pagination {
             amount: Some(10),
             // amount: Some(page_amount().0),
             favourites: Some(false),
+            drafts: Some(false),
         }
     }
 }
@@ -89,12 +101,13 @@ impl std::fmt::Display for Pagination {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "/?tag={}&my_feed={}&page={}&amount={}&favourites={}",
+            "/?tag={}&my_feed={}&page={}&amount={}&favourites={}&drafts={}",
             self.get_tag(),
             self.get_my_feed(),
             self.get_page(),
             self.get_amount(),
             self.get_favourites(),
+            self.get_drafts(),
         )
     }
 }
@@ -106,6 +119,7 @@ impl From<String> for Pagination {
         let mut page = Some(0);
         let mut amount = Some(10);
         let mut favourites = Some(false);
+        let mut drafts = Some(false);
 
         for param in url.split('&') {
             // dioxus::logger::tracing::info!("Param is: {param}");
@@ -116,6 +130,7 @@ impl From<String> for Pagination {
                     "page" => page = Some(value.parse().unwrap_or_default()),
                     "amount" => amount = Some(value.parse().unwrap_or_default()),
                     "favourites" => favourites = Some(value.parse().unwrap_or_default()),
+                    "drafts" => drafts = Some(value.parse().unwrap_or_default()),
                     _ => {}
                 }
             }
@@ -127,6 +142,7 @@ impl From<String> for Pagination {
             page,
             amount,
             favourites,
+            drafts,
         }
     }
 }
@@ -0,0 +1,84 @@
+/// An emailed password reset link. Only the hash of the token is stored, so a leaked
+/// database does not hand out working links.
+pub struct PasswordReset;
+
+impl PasswordReset {
+    /// `expires_at` is a UTC unix timestamp.
+    pub async fn insert(
+        token_hash: String,
+        username: String,
+        expires_at: i64,
+    ) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO PasswordResets(token_hash, username, expires_at) VALUES ($1, $2, datetime($3, 'unixepoch'))",
+            token_hash,
+            username,
+            expires_at
+        )
+        .execute(crate::database::server::get_db())
+        .await
+    }
+
+    /// The user a still usable token belongs to.
+    pub async fn username(token_hash: &str) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar!(
+            "SELECT username FROM PasswordResets
+            WHERE token_hash=$1 and consumed_at IS NULL and expires_at > CURRENT_TIMESTAMP",
+            token_hash
+        )
+        .fetch_optional(crate::database::server::get_db())
+        .await
+    }
+
+    /// Marks the token as used, `false` when it was already consumed or expired in between.
+    pub async fn consume(token_hash: &str) -> Result<bool, sqlx::Error> {
+        sqlx::query!(
+            "UPDATE PasswordResets SET consumed_at=CURRENT_TIMESTAMP
+            WHERE token_hash=$1 and consumed_at IS NULL and expires_at > CURRENT_TIMESTAMP",
+            token_hash
+        )
+        .execute(crate::database::server::get_db())
+        .await
+        .map(|x| x.rows_affected() == 1)
+    }
+
+    /// Drops every outstanding link of the user, once the password changed they are stale.
+    pub async fn delete_all(
+        username: &str,
+    ) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        sqlx::query!(
+            "DELETE FROM PasswordResets WHERE username=$1 and consumed_at IS NULL",
+            username
+        )
+        .execute(crate::database::server::get_db())
+        .await
+    }
+
+    /// Logs a reset request, whether or not the email belongs to someone, and returns how
+    /// many requests the email and the IP made in the last hour, this one included.
+    pub async fn record_request(email: &str, ip: &str) -> Result<(i64, i64), sqlx::Error> {
+        let db = crate::database::server::get_db();
+        sqlx::query!(
+            "DELETE FROM PasswordResetRequests WHERE requested_at < datetime('now', '-1 day')"
+        )
+        .execute(db)
+        .await?;
+        sqlx::query!(
+            "INSERT INTO PasswordResetRequests(email, ip) VALUES ($1, $2)",
+            email,
+            ip
+        )
+        .execute(db)
+        .await?;
+        let counts = sqlx::query!(
+            r#"SELECT
+            (SELECT count(*) FROM PasswordResetRequests WHERE email=$1 and requested_at > datetime('now', '-1 hour')) as "by_email!: i64",
+            (SELECT count(*) FROM PasswordResetRequests WHERE ip=$2 and requested_at > datetime('now', '-1 hour')) as "by_ip!: i64""#,
+            email,
+            ip
+        )
+        .fetch_one(db)
+        .await?;
+        Ok((counts.by_email, counts.by_ip))
+    }
+}
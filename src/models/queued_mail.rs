@@ -0,0 +1,114 @@
+use sqlx::types::chrono::NaiveDateTime;
+
+/// An email waiting in the `MailQueue` to be handed to the mail backend.
+#[derive(Debug, Clone)]
+pub struct QueuedMail {
+    pub id: i64,
+    pub recipient: String,
+    pub subject: String,
+    pub text_body: String,
+    pub html_body: String,
+    pub attempts: i64,
+    /// `None` once the mail was given up on.
+    pub next_attempt_at: Option<NaiveDateTime>,
+    pub last_error: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+impl QueuedMail {
+    /// `expires_at` is a UTC unix timestamp after which the mail is dropped, sent or not.
+    pub async fn insert(
+        recipient: &str,
+        subject: &str,
+        text_body: &str,
+        html_body: &str,
+        expires_at: Option<i64>,
+    ) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO MailQueue(recipient, subject, text_body, html_body, expires_at)
+            VALUES ($1, $2, $3, $4, datetime($5, 'unixepoch'))",
+            recipient,
+            subject,
+            text_body,
+            html_body,
+            expires_at
+        )
+        .execute(crate::database::server::get_db())
+        .await
+    }
+
+    /// The oldest mails whose next attempt is due.
+    pub async fn due(limit: i64) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT id as "id!", recipient, subject, text_body, html_body, attempts, next_attempt_at,
+                last_error, created_at
+            FROM MailQueue
+            WHERE next_attempt_at <= CURRENT_TIMESTAMP
+                AND (expires_at IS NULL OR expires_at > CURRENT_TIMESTAMP)
+            ORDER BY id
+            LIMIT $1"#,
+            limit
+        )
+        .fetch_all(crate::database::server::get_db())
+        .await
+    }
+
+    /// Everything still in the queue, including the mails that were given up on.
+    pub async fn all() -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT id as "id!", recipient, subject, text_body, html_body, attempts, next_attempt_at,
+                last_error, created_at
+            FROM MailQueue
+            ORDER BY id"#
+        )
+        .fetch_all(crate::database::server::get_db())
+        .await
+    }
+
+    /// Drops the mail whose links expired, including the mail given up on.
+    pub async fn delete_expired() -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        sqlx::query!("DELETE FROM MailQueue WHERE expires_at <= CURRENT_TIMESTAMP")
+            .execute(crate::database::server::get_db())
+            .await
+    }
+
+    pub async fn delivered(id: i64) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        sqlx::query!("DELETE FROM MailQueue WHERE id=$1", id)
+            .execute(crate::database::server::get_db())
+            .await
+    }
+
+    /// Records a failed attempt, the next one happens `retry_in` seconds from now or never
+    /// when it is `None`.
+    pub async fn failed(
+        id: i64,
+        error: &str,
+        retry_in: Option<i64>,
+    ) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        let retry_in = retry_in.map(|x| format!("+{x} seconds"));
+        sqlx::query!(
+            "UPDATE MailQueue
+            SET attempts=attempts+1, last_error=$2, next_attempt_at=datetime('now', $3)
+            WHERE id=$1",
+            id,
+            error,
+            retry_in
+        )
+        .execute(crate::database::server::get_db())
+        .await
+    }
+
+    /// Puts a mail that was given up on back in line, all of them when `id` is `None`.
+    pub async fn retry(id: Option<i64>) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        sqlx::query!(
+            "UPDATE MailQueue SET attempts=0, next_attempt_at=CURRENT_TIMESTAMP
+            WHERE next_attempt_at IS NULL AND ($1 IS NULL OR id=$1)
+                AND (expires_at IS NULL OR expires_at > CURRENT_TIMESTAMP)",
+            id
+        )
+        .execute(crate::database::server::get_db())
+        .await
+    }
+}
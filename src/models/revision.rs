@@ -0,0 +1,239 @@
+use serde::{Deserialize, Serialize};
+
+/// A saved version of an article, every save through the editor or the API adds one.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+pub struct Revision {
+    pub id: i64,
+    pub article: String,
+    pub author: String,
+    pub title: String,
+    pub description: String,
+    /// Left out when listing the history.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    pub tag_list: Vec<String>,
+    pub created_at: String,
+}
+
+/// A line of the difference between two revisions.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum DiffLine {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+impl Revision {
+    #[cfg(feature = "server")]
+    pub async fn insert(
+        conn: &mut sqlx::SqliteConnection,
+        article: &str,
+        author: &str,
+        title: &str,
+        description: &str,
+        body: &str,
+        tag_list: &str,
+    ) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO ArticleRevisions(article, author, title, description, body, tag_list)
+            VALUES ($1, $2, $3, $4, $5, $6)",
+            article,
+            author,
+            title,
+            description,
+            body,
+            tag_list
+        )
+        .execute(conn)
+        .await
+    }
+
+    /// The history of an article, newest first.
+    #[cfg(feature = "server")]
+    pub async fn for_article(article: &str) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query!(
+            r#"SELECT id as "id!", article, author, title, description, tag_list, created_at
+            FROM ArticleRevisions WHERE article=$1 ORDER BY id DESC"#,
+            article
+        )
+        .map(|x| Self {
+            id: x.id,
+            article: x.article,
+            author: x.author,
+            title: x.title,
+            description: x.description,
+            body: None,
+            tag_list: split_tags(&x.tag_list),
+            created_at: x.created_at.format(super::DATE_FORMAT).to_string(),
+        })
+        .fetch_all(crate::database::server::get_db())
+        .await
+    }
+
+    #[cfg(feature = "server")]
+    pub async fn get(article: &str, id: i64) -> Result<Self, sqlx::Error> {
+        sqlx::query!(
+            "SELECT * FROM ArticleRevisions WHERE article=$1 AND id=$2",
+            article,
+            id
+        )
+        .map(|x| Self {
+            id: x.id,
+            article: x.article,
+            author: x.author,
+            title: x.title,
+            description: x.description,
+            body: Some(x.body),
+            tag_list: split_tags(&x.tag_list),
+            created_at: x.created_at.format(super::DATE_FORMAT).to_string(),
+        })
+        .fetch_one(crate::database::server::get_db())
+        .await
+    }
+
+    /// Everything a revision holds as one text, what the diffs are made of.
+    #[cfg(feature = "server")]
+    fn text(&self) -> String {
+        format!(
+            "{}\n{}\ntags: {}\n\n{}",
+            self.title,
+            self.description,
+            self.tag_list.join(" "),
+            self.body.as_deref().unwrap_or_default()
+        )
+    }
+
+    /// Line diff from `self` to `newer`, `None` when they differ in too many lines to compare.
+    #[cfg(feature = "server")]
+    pub fn diff(&self, newer: &Self) -> Option<Vec<DiffLine>> {
+        diff_lines(&self.text(), &newer.text())
+    }
+}
+
+#[cfg(feature = "server")]
+fn split_tags(tag_list: &str) -> Vec<String> {
+    tag_list
+        .split_ascii_whitespace()
+        .map(str::to_string)
+        .collect()
+}
+
+/// The largest LCS table [`diff_lines`] builds, 16 MB of `u32`, about 2000 changed lines on
+/// each side.
+#[cfg(feature = "server")]
+const DIFF_MAX_CELLS: usize = 4_000_000;
+
+/// Longest common subsequence over the lines that differ, the common head and tail are
+/// taken out first so the table stays small for the usual edit. `None` when the table would
+/// be larger than [`DIFF_MAX_CELLS`].
+#[cfg(feature = "server")]
+fn diff_lines(old: &str, new: &str) -> Option<Vec<DiffLine>> {
+    let old = old.lines().collect::<Vec<_>>();
+    let new = new.lines().collect::<Vec<_>>();
+    let head = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let tail = old[head..]
+        .iter()
+        .rev()
+        .zip(new[head..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (a, b) = (&old[head..old.len() - tail], &new[head..new.len() - tail]);
+    if (a.len() + 1).saturating_mul(b.len() + 1) > DIFF_MAX_CELLS {
+        return None;
+    }
+
+    // lcs[i][j]: length of the longest common subsequence of a[i..] and b[j..]
+    let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = old[..head]
+        .iter()
+        .map(|x| DiffLine::Same(x.to_string()))
+        .collect::<Vec<_>>();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            lines.push(DiffLine::Same(a[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(DiffLine::Removed(a[i].to_string()));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(b[j].to_string()));
+            j += 1;
+        }
+    }
+    lines.extend(
+        old[old.len() - tail..]
+            .iter()
+            .map(|x| DiffLine::Same(x.to_string())),
+    );
+    Some(lines)
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_lines_keeps_common_lines() {
+        assert_eq!(
+            diff_lines("a\nb\nc\nd", "a\nx\nc\nd\ne"),
+            Some(vec![
+                DiffLine::Same("a".to_string()),
+                DiffLine::Removed("b".to_string()),
+                DiffLine::Added("x".to_string()),
+                DiffLine::Same("c".to_string()),
+                DiffLine::Same("d".to_string()),
+                DiffLine::Added("e".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn diff_lines_of_equal_and_empty_texts() {
+        assert_eq!(
+            diff_lines("a\nb", "a\nb"),
+            Some(vec![
+                DiffLine::Same("a".to_string()),
+                DiffLine::Same("b".to_string()),
+            ])
+        );
+        assert_eq!(diff_lines("", ""), Some(vec![]));
+        assert_eq!(
+            diff_lines("", "a"),
+            Some(vec![DiffLine::Added("a".to_string())])
+        );
+    }
+
+    #[test]
+    fn diff_lines_refuses_tables_over_the_cap() {
+        let old = (0..2500).map(|x| format!("old {x}\n")).collect::<String>();
+        let new = (0..2500).map(|x| format!("new {x}\n")).collect::<String>();
+        assert_eq!(diff_lines(&old, &new), None);
+    }
+
+    #[test]
+    fn diff_lines_only_counts_the_changed_lines_against_the_cap() {
+        let common = (0..10_000)
+            .map(|x| format!("line {x}\n"))
+            .collect::<String>();
+        let lines = diff_lines(
+            &format!("{common}old\n{common}"),
+            &format!("{common}new\n{common}"),
+        )
+        .expect("two changed lines fit");
+        assert_eq!(lines.len(), 20_002);
+        assert!(lines.contains(&DiffLine::Removed("old".to_string())));
+        assert!(lines.contains(&DiffLine::Added("new".to_string())));
+    }
+}
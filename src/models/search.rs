@@ -1,11 +1,50 @@
 use serde::{Deserialize, Serialize};
 
+/// A piece of a search snippet, `matched` when it is part of the searched terms.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+pub struct HighlightSegment {
+    pub text: String,
+    pub matched: bool,
+}
+
 #[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
 pub struct MatchedArticles {
     pub slug: String,
-    pub title: Option<String>,
-    pub description: Option<String>,
-    pub body: Option<String>,
+    pub title: Vec<HighlightSegment>,
+    pub description: Vec<HighlightSegment>,
+    pub body: Vec<HighlightSegment>,
+}
+
+// fts5 wraps matches in these markers, control characters won't show up in the text
+#[cfg(feature = "server")]
+const MATCH_START: char = '\u{2}';
+#[cfg(feature = "server")]
+const MATCH_END: char = '\u{3}';
+
+#[cfg(feature = "server")]
+fn split_snippet(snippet: Option<String>) -> Vec<HighlightSegment> {
+    let mut segments = vec![];
+    let mut rest = snippet.as_deref().unwrap_or_default();
+    while !rest.is_empty() {
+        let (text, matched, next) = match rest.strip_prefix(MATCH_START) {
+            Some(inner) => {
+                let (text, next) = inner.split_once(MATCH_END).unwrap_or((inner, ""));
+                (text, true, next)
+            }
+            None => {
+                let (text, next) = rest.split_at(rest.find(MATCH_START).unwrap_or(rest.len()));
+                (text, false, next)
+            }
+        };
+        if !text.is_empty() {
+            segments.push(HighlightSegment {
+                text: text.to_string(),
+                matched,
+            });
+        }
+        rest = next;
+    }
+    segments
 }
 
 impl MatchedArticles {
@@ -22,12 +61,12 @@ impl MatchedArticles {
             r#"
 SELECT distinct
 a.slug as slug,
-snippet(articles_fts,1, '<span class="bg-yellow-300 text-gray-700">','</span>','<span class="bg-yellow-300 text-gray-700">  ...  </span>',10) as "title: String",
-snippet(articles_fts,2, '<span class="bg-yellow-300 text-gray-700">','</span>','<span class="bg-yellow-300 text-gray-700">  ...  </span>',20) as "description: String",
-snippet(articles_fts,3, '<span class="bg-yellow-300 text-gray-700">','</span>','<span class="bg-yellow-300 text-gray-700">  ...  </span>',20) as "body: String"
+snippet(articles_fts,1, char(2), char(3), '  ...  ',10) as "title: String",
+snippet(articles_fts,2, char(2), char(3), '  ...  ',20) as "description: String",
+snippet(articles_fts,3, char(2), char(3), '  ...  ',20) as "body: String"
 FROM Articles_fts AS AFTS
 JOIN  Articles AS A  ON A.oid = AFTS.rowid
-WHERE Articles_fts MATCH $3
+WHERE Articles_fts MATCH $3 AND A.status = 'published'
 order by rank
 LIMIT $1 OFFSET $2"#,
             amount,
@@ -36,11 +75,92 @@ LIMIT $1 OFFSET $2"#,
         )
         .map(|x| Self {
             slug: x.slug,
-            title: x.title,
-            description: x.description,
-            body: x.body,
+            title: split_snippet(x.title),
+            description: split_snippet(x.description),
+            body: split_snippet(x.body),
         })
         .fetch_all(crate::database::server::get_db())
         .await
     }
 }
+
+/// Rebuilds both full-text indexes from their content tables, for when they drifted.
+/// The article index only holds published articles, so it is refilled by hand instead of
+/// with fts5's `rebuild` which would pick up the drafts too.
+#[cfg(feature = "server")]
+pub async fn rebuild_search_index() -> Result<(), sqlx::Error> {
+    let db = crate::database::server::get_db();
+    let mut transaction = db.begin().await?;
+    sqlx::query!("INSERT INTO Articles_fts(Articles_fts) VALUES('delete-all')")
+        .execute(transaction.as_mut())
+        .await?;
+    sqlx::query!(
+        "INSERT INTO Articles_fts(rowid, slug, title, description, body)
+        SELECT oid, slug, title, description, body FROM Articles WHERE status = 'published'"
+    )
+    .execute(transaction.as_mut())
+    .await?;
+    transaction.commit().await?;
+    sqlx::query!("INSERT INTO articletags_fts(articletags_fts) VALUES('rebuild')")
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+/// Merges the index segments of both full-text indexes.
+#[cfg(feature = "server")]
+pub async fn optimize_search_index() -> Result<(), sqlx::Error> {
+    let db = crate::database::server::get_db();
+    sqlx::query!("INSERT INTO Articles_fts(Articles_fts) VALUES('optimize')")
+        .execute(db)
+        .await?;
+    sqlx::query!("INSERT INTO articletags_fts(articletags_fts) VALUES('optimize')")
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use super::*;
+
+    fn segment(text: &str, matched: bool) -> HighlightSegment {
+        HighlightSegment {
+            text: text.to_string(),
+            matched,
+        }
+    }
+
+    #[test]
+    fn split_snippet_marks_matches() {
+        let snippet = format!("a {MATCH_START}dog{MATCH_END} and a {MATCH_START}cat{MATCH_END}");
+        assert_eq!(
+            split_snippet(Some(snippet)),
+            vec![
+                segment("a ", false),
+                segment("dog", true),
+                segment(" and a ", false),
+                segment("cat", true),
+            ]
+        );
+    }
+
+    #[test]
+    fn split_snippet_without_matches() {
+        assert_eq!(
+            split_snippet(Some("plain text".to_string())),
+            vec![segment("plain text", false)]
+        );
+        assert_eq!(split_snippet(Some(String::new())), vec![]);
+        assert_eq!(split_snippet(None), vec![]);
+    }
+
+    #[test]
+    fn split_snippet_keeps_an_unterminated_match() {
+        let snippet = format!("{MATCH_START}dog{MATCH_END}{MATCH_START}house");
+        assert_eq!(
+            split_snippet(Some(snippet)),
+            vec![segment("dog", true), segment("house", true)]
+        );
+    }
+}
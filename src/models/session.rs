@@ -0,0 +1,127 @@
+/// A login, the `id` is the `jti` claim of the token handed out to the browser or API client.
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq, Default)]
+pub struct Session {
+    pub id: String,
+    pub created_at: String,
+    pub last_seen: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    /// The session making the request.
+    pub current: bool,
+}
+
+impl Session {
+    /// `expires_at` is a UTC unix timestamp, the same one that goes into the token `exp` claim.
+    #[cfg(feature = "server")]
+    pub async fn insert(
+        id: String,
+        username: String,
+        expires_at: i64,
+        user_agent: Option<String>,
+        ip: Option<String>,
+    ) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO Sessions(id, username, expires_at, last_seen, user_agent, ip)
+            VALUES ($1, $2, datetime($3, 'unixepoch'), CURRENT_TIMESTAMP, $4, $5)",
+            id,
+            username,
+            expires_at,
+            user_agent,
+            ip
+        )
+        .execute(crate::database::server::get_db())
+        .await
+    }
+
+    /// Active sessions of `username`, most recently used first.
+    #[cfg(feature = "server")]
+    pub async fn for_user(username: &str, current_id: &str) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query!(
+            "SELECT id, created_at, last_seen, user_agent, ip FROM Sessions
+            WHERE username=$1 and expires_at > CURRENT_TIMESTAMP
+            ORDER BY coalesce(last_seen, created_at) DESC",
+            username
+        )
+        .map(|x| Self {
+            current: x.id == current_id,
+            id: x.id,
+            created_at: x.created_at.format(super::DATE_FORMAT).to_string(),
+            last_seen: x
+                .last_seen
+                .map(|x| x.format(super::DATE_FORMAT).to_string()),
+            user_agent: x.user_agent,
+            ip: x.ip,
+        })
+        .fetch_all(crate::database::server::get_db())
+        .await
+    }
+
+    #[cfg(feature = "server")]
+    pub async fn is_active(id: &str, username: &str) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar!(
+            "SELECT EXISTS(SELECT 1 FROM Sessions WHERE id=$1 and username=$2 and expires_at > CURRENT_TIMESTAMP)",
+            id,
+            username
+        )
+        .fetch_one(crate::database::server::get_db())
+        .await
+        .map(|x| x != 0)
+    }
+
+    /// Records activity on the session, at most once a minute to keep writes down.
+    #[cfg(feature = "server")]
+    pub async fn touch(id: &str) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        sqlx::query!(
+            "UPDATE Sessions SET last_seen=CURRENT_TIMESTAMP
+            WHERE id=$1 and (last_seen IS NULL or last_seen < datetime('now', '-1 minute'))",
+            id
+        )
+        .execute(crate::database::server::get_db())
+        .await
+    }
+
+    #[cfg(feature = "server")]
+    pub async fn delete(
+        id: &str,
+        username: &str,
+    ) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        sqlx::query!(
+            "DELETE FROM Sessions WHERE id=$1 and username=$2",
+            id,
+            username
+        )
+        .execute(crate::database::server::get_db())
+        .await
+    }
+
+    /// Signs the user out everywhere, used whenever the password changes.
+    #[cfg(feature = "server")]
+    pub async fn delete_all(
+        username: &str,
+    ) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        sqlx::query!("DELETE FROM Sessions WHERE username=$1", username)
+            .execute(crate::database::server::get_db())
+            .await
+    }
+
+    #[cfg(feature = "server")]
+    pub async fn delete_all_except(
+        username: &str,
+        id: &str,
+    ) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        sqlx::query!(
+            "DELETE FROM Sessions WHERE username=$1 and id<>$2",
+            username,
+            id
+        )
+        .execute(crate::database::server::get_db())
+        .await
+    }
+
+    #[cfg(feature = "server")]
+    pub async fn delete_expired() -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        sqlx::query!("DELETE FROM Sessions WHERE expires_at <= CURRENT_TIMESTAMP")
+            .execute(crate::database::server::get_db())
+            .await
+    }
+}
@@ -0,0 +1,253 @@
+/// The TOTP second factor of a user and their recovery codes. Codes are checked in
+/// `crate::auth`, this only keeps the state.
+pub struct TwoFactor;
+
+impl TwoFactor {
+    /// The base32 secret of a user who turned the second factor on.
+    pub async fn secret(username: &str) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar!("SELECT totp_secret FROM Users WHERE username=$1", username)
+            .fetch_optional(crate::database::server::get_db())
+            .await
+            .map(Option::flatten)
+    }
+
+    /// Keeps `secret` until a first code proves the authenticator has it.
+    pub async fn start_enrollment(
+        username: &str,
+        secret: &str,
+    ) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        sqlx::query!(
+            "UPDATE Users SET totp_pending_secret=$2 WHERE username=$1",
+            username,
+            secret
+        )
+        .execute(crate::database::server::get_db())
+        .await
+    }
+
+    pub async fn pending_secret(username: &str) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar!(
+            "SELECT totp_pending_secret FROM Users WHERE username=$1",
+            username
+        )
+        .fetch_optional(crate::database::server::get_db())
+        .await
+        .map(Option::flatten)
+    }
+
+    /// Turns the enrolled secret on, `step` being the time step of the code that confirmed
+    /// it, and replaces the recovery codes. `false` when there was no enrollment going on.
+    pub async fn enable(
+        username: &str,
+        step: i64,
+        recovery_code_hashes: &[String],
+    ) -> Result<bool, sqlx::Error> {
+        let mut transaction = crate::database::server::get_db().begin().await?;
+        let enabled = sqlx::query!(
+            "UPDATE Users
+            SET totp_secret=totp_pending_secret, totp_pending_secret=NULL, totp_last_step=$2
+            WHERE username=$1 and totp_pending_secret IS NOT NULL",
+            username,
+            step
+        )
+        .execute(transaction.as_mut())
+        .await?
+        .rows_affected();
+        if enabled == 0 {
+            return Ok(false);
+        }
+        Self::insert_recovery_codes(&mut transaction, username, recovery_code_hashes).await?;
+        transaction.commit().await?;
+        Ok(true)
+    }
+
+    pub async fn disable(username: &str) -> Result<(), sqlx::Error> {
+        let mut transaction = crate::database::server::get_db().begin().await?;
+        sqlx::query!(
+            "UPDATE Users
+            SET totp_secret=NULL, totp_pending_secret=NULL, totp_last_step=NULL,
+                totp_failures=0, totp_locked_until=NULL
+            WHERE username=$1",
+            username
+        )
+        .execute(transaction.as_mut())
+        .await?;
+        sqlx::query!("DELETE FROM RecoveryCodes WHERE username=$1", username)
+            .execute(transaction.as_mut())
+            .await?;
+        transaction.commit().await
+    }
+
+    /// Accepts a code of time step `step` only when no code of that step or a later one was
+    /// accepted before, so a code seen over a shoulder does not work a second time.
+    pub async fn use_step(
+        conn: &mut sqlx::SqliteConnection,
+        username: &str,
+        step: i64,
+    ) -> Result<bool, sqlx::Error> {
+        sqlx::query!(
+            "UPDATE Users SET totp_last_step=$2
+            WHERE username=$1 and totp_secret IS NOT NULL
+                and (totp_last_step IS NULL or totp_last_step < $2)",
+            username,
+            step
+        )
+        .execute(conn)
+        .await
+        .map(|x| x.rows_affected() == 1)
+    }
+
+    /// Whether too many wrong codes locked the second factor of `username` for now.
+    pub async fn locked(username: &str) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"SELECT count(*) as "count!: i64" FROM Users
+            WHERE username=$1 and totp_locked_until > CURRENT_TIMESTAMP"#,
+            username
+        )
+        .fetch_one(crate::database::server::get_db())
+        .await
+        .map(|x| x > 0)
+    }
+
+    /// Counts a wrong code. The `max_failures`th one in a row locks the second factor for
+    /// `lock_secs` seconds and starts the count over.
+    pub async fn record_failure(
+        username: &str,
+        max_failures: i64,
+        lock_secs: i64,
+    ) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        sqlx::query!(
+            "UPDATE Users
+            SET totp_locked_until=CASE WHEN totp_failures + 1 >= $2
+                    THEN datetime('now', '+' || $3 || ' seconds') ELSE totp_locked_until END,
+                totp_failures=CASE WHEN totp_failures + 1 >= $2 THEN 0 ELSE totp_failures + 1 END
+            WHERE username=$1",
+            username,
+            max_failures,
+            lock_secs
+        )
+        .execute(crate::database::server::get_db())
+        .await
+    }
+
+    pub async fn reset_failures(
+        username: &str,
+    ) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        sqlx::query!(
+            "UPDATE Users SET totp_failures=0 WHERE username=$1 and totp_failures > 0",
+            username
+        )
+        .execute(crate::database::server::get_db())
+        .await
+    }
+
+    /// Uses up a recovery code, `false` when it is unknown or was used already.
+    pub async fn use_recovery_code(username: &str, code_hash: &str) -> Result<bool, sqlx::Error> {
+        sqlx::query!(
+            "UPDATE RecoveryCodes SET used_at=CURRENT_TIMESTAMP
+            WHERE username=$1 and code_hash=$2 and used_at IS NULL",
+            username,
+            code_hash
+        )
+        .execute(crate::database::server::get_db())
+        .await
+        .map(|x| x.rows_affected() == 1)
+    }
+
+    pub async fn unused_recovery_codes(username: &str) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"SELECT count(*) as "count!: i64" FROM RecoveryCodes
+            WHERE username=$1 and used_at IS NULL"#,
+            username
+        )
+        .fetch_one(crate::database::server::get_db())
+        .await
+    }
+
+    pub async fn replace_recovery_codes(
+        username: &str,
+        recovery_code_hashes: &[String],
+    ) -> Result<(), sqlx::Error> {
+        let mut transaction = crate::database::server::get_db().begin().await?;
+        Self::insert_recovery_codes(&mut transaction, username, recovery_code_hashes).await?;
+        transaction.commit().await
+    }
+
+    async fn insert_recovery_codes(
+        transaction: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+        username: &str,
+        recovery_code_hashes: &[String],
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!("DELETE FROM RecoveryCodes WHERE username=$1", username)
+            .execute(transaction.as_mut())
+            .await?;
+        let hashes = serde_json::to_string(recovery_code_hashes).unwrap_or_default();
+        sqlx::query!(
+            "INSERT INTO RecoveryCodes(code_hash, username)
+            SELECT value, $1 FROM json_each($2)",
+            username,
+            hashes
+        )
+        .execute(transaction.as_mut())
+        .await?;
+        Ok(())
+    }
+}
+
+/// A login that passed the password step and waits for the second factor. Only the hash of
+/// its token is stored.
+pub struct MfaChallenge;
+
+impl MfaChallenge {
+    /// `expires_at` is a UTC unix timestamp.
+    pub async fn insert(
+        token_hash: &str,
+        username: &str,
+        expires_at: i64,
+    ) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        let db = crate::database::server::get_db();
+        sqlx::query!("DELETE FROM MfaChallenges WHERE expires_at < CURRENT_TIMESTAMP")
+            .execute(db)
+            .await?;
+        sqlx::query!(
+            "INSERT INTO MfaChallenges(token_hash, username, expires_at)
+            VALUES ($1, $2, datetime($3, 'unixepoch'))",
+            token_hash,
+            username,
+            expires_at
+        )
+        .execute(db)
+        .await
+    }
+
+    /// Counts an attempt at the second factor and returns the user waiting for it, `None`
+    /// once the challenge expired or ran out of attempts.
+    pub async fn attempt(
+        token_hash: &str,
+        max_attempts: i64,
+    ) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar!(
+            "UPDATE MfaChallenges SET attempts=attempts+1
+            WHERE token_hash=$1 and attempts < $2 and expires_at > CURRENT_TIMESTAMP
+            RETURNING username",
+            token_hash,
+            max_attempts
+        )
+        .fetch_optional(crate::database::server::get_db())
+        .await
+    }
+
+    pub async fn delete(token_hash: &str) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        sqlx::query!("DELETE FROM MfaChallenges WHERE token_hash=$1", token_hash)
+            .execute(crate::database::server::get_db())
+            .await
+    }
+
+    pub async fn delete_all(
+        username: &str,
+    ) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        sqlx::query!("DELETE FROM MfaChallenges WHERE username=$1", username)
+            .execute(crate::database::server::get_db())
+            .await
+    }
+}
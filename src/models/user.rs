@@ -20,6 +20,20 @@ fn default_theme() -> String {
     "dark".to_string()
 }
 
+fn default_email_digest() -> String {
+    "off".to_string()
+}
+
+/// How often the notification digest can be sent.
+pub const EMAIL_DIGESTS: [&str; 3] = ["off", "daily", "weekly"];
+
+fn default_locale() -> String {
+    "en".to_string()
+}
+
+/// The languages mail can be written in, with their names in that language.
+pub const LOCALES: [(&str, &str); 2] = [("en", "English"), ("fr", "Français")];
+
 #[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
 pub struct User {
     pub username: String,
@@ -33,6 +47,18 @@ pub struct User {
     per_page_amount: i64,
     #[serde(default = "default_theme")]
     theme_mode: String,
+    /// One of [`EMAIL_DIGESTS`].
+    #[serde(default = "default_email_digest")]
+    email_digest: String,
+    /// One of [`LOCALES`], for the mail sent to the user.
+    #[serde(default = "default_locale")]
+    locale: String,
+    /// Whether the owner confirmed `email`, no mail goes to it before.
+    #[serde(default)]
+    email_verified: bool,
+    /// An address waiting to be confirmed before it replaces `email`.
+    #[serde(default)]
+    pending_email: Option<String>,
 }
 #[cfg(feature = "server")]
 static EMAIL_REGEX: std::sync::OnceLock<regex::Regex> = std::sync::OnceLock::new();
@@ -50,6 +76,24 @@ impl User {
     pub fn theme_mode(&self) -> String {
         self.theme_mode.clone()
     }
+
+    pub fn email_digest(&self) -> String {
+        self.email_digest.clone()
+    }
+
+    pub fn locale(&self) -> String {
+        self.locale.clone()
+    }
+
+    #[inline]
+    pub fn email_verified(&self) -> bool {
+        self.email_verified
+    }
+
+    #[inline]
+    pub fn pending_email(&self) -> Option<String> {
+        self.pending_email.clone()
+    }
     #[inline]
     pub fn email(&self) -> String {
         self.email.to_string()
@@ -84,7 +128,7 @@ impl User {
     }
 
     #[cfg(feature = "server")]
-    fn validate_email(email: &str) -> bool {
+    pub(crate) fn validate_email(email: &str) -> bool {
         EMAIL_REGEX
             .get_or_init(|| regex::Regex::new(r"^[\w\-\.]+@([\w-]+\.)+\w{2,4}$").unwrap())
             .is_match(email)
@@ -135,6 +179,24 @@ impl User {
         self
     }
 
+    #[cfg(feature = "server")]
+    pub fn set_email_digest(mut self, email_digest: String) -> Result<Self, String> {
+        if !EMAIL_DIGESTS.contains(&email_digest.as_str()) {
+            return Err(format!("Unknown email digest {email_digest}"));
+        }
+        self.email_digest = email_digest;
+        Ok(self)
+    }
+
+    #[cfg(feature = "server")]
+    pub fn set_locale(mut self, locale: String) -> Result<Self, String> {
+        if !LOCALES.iter().any(|(x, _)| *x == locale) {
+            return Err(format!("Unknown language {locale}"));
+        }
+        self.locale = locale;
+        Ok(self)
+    }
+
     #[cfg(feature = "server")]
     #[inline]
     pub fn set_theme_mode(mut self, theme: String) -> Self {
@@ -142,26 +204,38 @@ impl User {
         self
     }
 
+    /// The stored hash must never round-trip into `update`, which would hash it again.
+    #[cfg(feature = "server")]
+    #[inline]
+    fn forget_password(self) -> Self {
+        Self {
+            password: None,
+            ..self
+        }
+    }
+
     #[cfg(feature = "server")]
     pub async fn get(username: String) -> Result<Self, sqlx::Error> {
         sqlx::query_as!(
             Self,
-            "SELECT username, email, bio, image, password, per_page_amount, theme_mode FROM users WHERE username=$1",
+            "SELECT username, email, bio, image, password, per_page_amount, theme_mode, email_digest, locale, email_verified, pending_email FROM users WHERE username=$1",
             username
         )
         .fetch_one(crate::database::server::get_db())
         .await
+        .map(Self::forget_password)
     }
 
     #[cfg(feature = "server")]
     pub async fn get_email(email: String) -> Result<Self, sqlx::Error> {
         sqlx::query_as!(
             Self,
-            "SELECT username, email, bio, image, password, per_page_amount, theme_mode FROM users WHERE email=$1",
+            "SELECT username, email, bio, image, password, per_page_amount, theme_mode, email_digest, locale, email_verified, pending_email FROM users WHERE email=$1",
             email
         )
         .fetch_one(crate::database::server::get_db())
         .await
+        .map(Self::forget_password)
     }
 
     #[cfg(feature = "server")]
@@ -225,6 +299,28 @@ WHERE username=$1",
         .await
     }
 
+    #[cfg(feature = "server")]
+    pub async fn delete(username: &str) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        sqlx::query!("DELETE FROM Users WHERE username=$1", username)
+            .execute(crate::database::server::get_db())
+            .await
+    }
+
+    /// Banned users keep their content but cannot log in anymore.
+    #[cfg(feature = "server")]
+    pub async fn set_banned(
+        username: &str,
+        banned: bool,
+    ) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        sqlx::query!(
+            "UPDATE Users SET banned_at=CASE WHEN $2 THEN coalesce(banned_at, CURRENT_TIMESTAMP) END WHERE username=$1",
+            username,
+            banned
+        )
+        .execute(crate::database::server::get_db())
+        .await
+    }
+
     #[cfg(feature = "server")]
     pub async fn update_per_page_amount(
         &self,
@@ -248,4 +344,102 @@ WHERE username=$1",
         .execute(crate::database::server::get_db())
         .await
     }
+
+    /// The first digest after turning it on covers what happens from then on.
+    #[cfg(feature = "server")]
+    pub async fn update_email_digest(
+        &self,
+    ) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        sqlx::query!(
+            "UPDATE Users SET
+                last_digest_at=CASE WHEN email_digest='off' THEN CURRENT_TIMESTAMP ELSE last_digest_at END,
+                email_digest=$2
+            WHERE username=$1",
+            self.username,
+            self.email_digest,
+        )
+        .execute(crate::database::server::get_db())
+        .await
+    }
+
+    #[cfg(feature = "server")]
+    pub async fn update_locale(&self) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        sqlx::query!(
+            "UPDATE Users SET locale=$2 WHERE username=$1",
+            self.username,
+            self.locale,
+        )
+        .execute(crate::database::server::get_db())
+        .await
+    }
+
+    /// The address waiting for confirmation, `None` to give up on it.
+    #[cfg(feature = "server")]
+    pub async fn update_pending_email(
+        username: &str,
+        email: Option<&str>,
+    ) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        sqlx::query!(
+            "UPDATE Users SET pending_email=$2 WHERE username=$1",
+            username,
+            email
+        )
+        .execute(crate::database::server::get_db())
+        .await
+    }
+
+    /// Trusts the current address without a confirmation, for accounts made by operators.
+    #[cfg(feature = "server")]
+    pub async fn mark_email_verified(
+        username: &str,
+    ) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        sqlx::query!(
+            "UPDATE Users SET email_verified=1 WHERE username=$1",
+            username
+        )
+        .execute(crate::database::server::get_db())
+        .await
+    }
+
+    /// Users whose daily or weekly digest is due, banned ones get none.
+    #[cfg(feature = "server")]
+    pub async fn due_digests() -> Result<Vec<DigestRecipient>, sqlx::Error> {
+        sqlx::query_as!(
+            DigestRecipient,
+            r#"SELECT username, email, locale,
+                coalesce(last_digest_at, datetime('now', '-7 days')) as "since!: sqlx::types::chrono::NaiveDateTime"
+            FROM Users
+            WHERE banned_at IS NULL AND email_verified AND (
+                (email_digest='daily' AND coalesce(last_digest_at, 0) <= datetime('now', '-1 day'))
+                OR (email_digest='weekly' AND coalesce(last_digest_at, 0) <= datetime('now', '-7 days'))
+            )"#
+        )
+        .fetch_all(crate::database::server::get_db())
+        .await
+    }
+
+    /// The next digest of `username` starts at `until`.
+    #[cfg(feature = "server")]
+    pub async fn digest_sent(
+        username: &str,
+        until: sqlx::types::chrono::NaiveDateTime,
+    ) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        sqlx::query!(
+            "UPDATE Users SET last_digest_at=$2 WHERE username=$1",
+            username,
+            until
+        )
+        .execute(crate::database::server::get_db())
+        .await
+    }
+}
+
+/// A user to send the notification digest to, covering what happened after `since`.
+#[cfg(feature = "server")]
+#[derive(Debug)]
+pub struct DigestRecipient {
+    pub username: String,
+    pub email: String,
+    pub locale: String,
+    pub since: sqlx::types::chrono::NaiveDateTime,
 }
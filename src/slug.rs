@@ -0,0 +1,134 @@
+//! URL slugs derived from article titles: lowercase ASCII words joined with `-`, and the
+//! redirects from the slugs an article had before its title changed.
+use axum::{
+    extract::Request,
+    http::Method,
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+/// Longest slug before the de-duplication suffix, cut at a word boundary.
+const MAX_LENGTH: usize = 80;
+
+/// Letters that do not decompose into an ASCII base letter.
+fn transliterate(c: char) -> Option<&'static str> {
+    Some(match c {
+        'ß' => "ss",
+        'æ' | 'Æ' => "ae",
+        'œ' | 'Œ' => "oe",
+        'ø' | 'Ø' => "o",
+        'đ' | 'Đ' | 'ð' | 'Ð' => "d",
+        'ł' | 'Ł' => "l",
+        'þ' | 'Þ' => "th",
+        'ı' => "i",
+        '&' => "and",
+        _ => return None,
+    })
+}
+
+/// The slug for a title, empty when nothing of it can be written in ASCII.
+pub fn slugify(title: &str) -> String {
+    let mut ascii = String::with_capacity(title.len());
+    for c in title.nfkd().filter(|x| !is_combining_mark(*x)) {
+        match transliterate(c) {
+            Some(x) => ascii.push_str(x),
+            None if c.is_ascii_alphanumeric() => ascii.push(c.to_ascii_lowercase()),
+            None => ascii.push(' '),
+        }
+    }
+
+    let mut slug = String::new();
+    for word in ascii.split_whitespace() {
+        if !slug.is_empty() && slug.len() + 1 + word.len() > MAX_LENGTH {
+            break;
+        }
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.push_str(word);
+    }
+    slug.truncate(MAX_LENGTH);
+    slug
+}
+
+/// Whether `slug` is `base` itself or `base` with a de-duplication suffix, so a title change
+/// that slugifies the same keeps the current slug.
+pub fn derives_from(slug: &str, base: &str) -> bool {
+    slug == base
+        || slug
+            .strip_prefix(base)
+            .and_then(|x| x.strip_prefix('-'))
+            .is_some_and(|x| x.parse::<u32>().is_ok_and(|x| x > 1))
+}
+
+/// Candidates for `base`, the first one nobody uses wins.
+pub fn candidates(base: &str) -> impl Iterator<Item = String> + '_ {
+    std::iter::once(base.to_string()).chain((2..).map(move |x| format!("{base}-{x}")))
+}
+
+/// Sends `/article/<old slug>[/...]` pages to the article's current slug for good.
+pub async fn redirect_old_slugs(request: Request, next: Next) -> Response {
+    if request.method() == Method::GET {
+        if let Some(rest) = request.uri().path().strip_prefix("/article/") {
+            let (slug, tail) = rest.split_once('/').unwrap_or((rest, ""));
+            let slug = urlencoding::decode(slug).unwrap_or_default();
+            if let Ok(Some(current)) = crate::models::Article::renamed_to(&slug).await {
+                let mut location = format!("/article/{}", urlencoding::encode(&current));
+                if !tail.is_empty() {
+                    location.push('/');
+                    location.push_str(tail);
+                }
+                if let Some(query) = request.uri().query() {
+                    location.push('?');
+                    location.push_str(query);
+                }
+                return Redirect::permanent(&location).into_response();
+            }
+        }
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slugify_writes_titles_in_ascii() {
+        assert_eq!(slugify("Hello, World!"), "hello-world");
+        assert_eq!(slugify("  Crème brûlée   à Paris "), "creme-brulee-a-paris");
+        assert_eq!(slugify("Straße & Smørrebrød"), "strasse-and-smorrebrod");
+        assert_eq!(slugify("Rust 2024: what's new?"), "rust-2024-what-s-new");
+    }
+
+    #[test]
+    fn slugify_is_empty_without_ascii() {
+        assert_eq!(slugify("日本語"), "");
+        assert_eq!(slugify("!!!"), "");
+    }
+
+    #[test]
+    fn slugify_cuts_long_titles_at_a_word() {
+        let slug = slugify(&"word ".repeat(30));
+        assert!(slug.len() <= MAX_LENGTH);
+        assert!(slug.ends_with("word"));
+        assert_eq!(slugify(&"x".repeat(100)).len(), MAX_LENGTH);
+    }
+
+    #[test]
+    fn derives_from_accepts_the_suffixes_of_candidates() {
+        assert!(derives_from("my-title", "my-title"));
+        assert!(derives_from("my-title-2", "my-title"));
+        assert!(derives_from("my-title-15", "my-title"));
+        assert!(!derives_from("my-title-1", "my-title"));
+        assert!(!derives_from("my-title-0", "my-title"));
+        assert!(!derives_from("my-title-two", "my-title"));
+        assert!(!derives_from("my-titles", "my-title"));
+        assert!(!derives_from("my", "my-title"));
+        assert!(candidates("my-title")
+            .skip(1)
+            .take(3)
+            .all(|x| derives_from(&x, "my-title")));
+    }
+}
@@ -15,20 +15,46 @@ pub struct ArticleDetailed {
 #[post("/api/get_article",  header: TypedHeader<Cookie>)]
 pub async fn get_article(slug: String) -> ServerFnResult<ArticleDetailed> {
     Ok(ArticleDetailed {
-        article: crate::models::Article::for_article(slug, header.clone())
-            .await
-            .map_err(|x| {
-                let err = format!("Error while getting user_profile articles: {x:?}");
-                tracing::error!("{err}");
-                ServerFnError::new("Could not retrieve articles, try again later")
-            })?,
+        article: crate::models::Article::for_article(
+            slug,
+            crate::auth::get_username_from_cookie(header).await,
+        )
+        .await
+        .map_err(|x| {
+            let err = format!("Error while getting user_profile articles: {x:?}");
+            tracing::error!("{err}");
+            ServerFnError::new("Could not retrieve articles, try again later")
+        })?,
         logged_user: crate::auth::current_user().await.unwrap(),
     })
 }
 
+/// Slug of the article behind `slug` if `logged_user` may see it, drafts and scheduled
+/// articles only exist for their author.
+#[cfg(feature = "server")]
+pub(crate) async fn visible_slug(
+    slug: String,
+    logged_user: Option<String>,
+) -> Result<String, ServerFnError> {
+    match crate::models::Article::for_article(slug, logged_user).await {
+        Ok(article) => Ok(article.slug),
+        Err(sqlx::Error::RowNotFound) => Err(ServerFnError::ServerError {
+            message: "article not found".to_string(),
+            code: 404,
+            details: None,
+        }),
+        Err(x) => {
+            tracing::error!("Error while looking up an article: {x:?}");
+            Err(ServerFnError::new(
+                "Could not retrieve the article, try again later",
+            ))
+        }
+    }
+}
+
 #[post("/api/post_comment", header: TypedHeader<Cookie>)]
-pub async fn post_comment(slug: String, body: String) -> ServerFnResult<()> {
-    let Some(logged_user) = crate::auth::get_username_from_cookie(header) else {
+pub async fn post_comment(slug: String, body: String, parent: Option<i64>) -> ServerFnResult<()> {
+    let Some(logged_user) = crate::auth::get_username_from_cookie(header).await else {
         return Err(ServerFnError::ServerError {
             message: "you must be logged in".to_string(),
             code: 401,
@@ -39,19 +65,58 @@ pub async fn post_comment(slug: String, body: String) -> ServerFnResult<()> {
         });
     };
 
-    crate::models::Comment::insert(slug, logged_user, body)
+    let slug = visible_slug(slug, Some(logged_user.clone())).await?;
+    let inserted = crate::models::Comment::insert(slug, logged_user, body, parent)
         .await
-        .map(|_| ())
         .map_err(|x| {
             let err = format!("Error while posting a comment: {x:?}");
             tracing::error!("{err}");
             ServerFnError::new("Could not post a comment, try again later")
-        })
+        })?
+        .rows_affected();
+    if inserted == 0 {
+        return Err(ServerFnError::new(
+            "The comment you reply to was deleted in the meantime",
+        ));
+    }
+    Ok(())
 }
 
+/// Only the author can edit a comment, and only until its edit window closes.
 #[tracing::instrument]
-#[post("/api/get_comments")]
+#[post("/api/edit_comment", header: TypedHeader<Cookie>)]
+pub async fn edit_comment(id: i64, body: String) -> Result<(), ServerFnError> {
+    let Some(logged_user) = crate::auth::get_username_from_cookie(header).await else {
+        return Err(ServerFnError::ServerError {
+            message: "you must be logged in".to_string(),
+            code: 401,
+            details: serde_json::json!(format!(
+                "Unauthorized: you must be logged to do this change"
+            ))
+            .into(),
+        });
+    };
+    if body.trim().is_empty() {
+        return Err(ServerFnError::new("The comment can not be empty"));
+    }
+
+    let edited = crate::models::Comment::update(id, logged_user, body)
+        .await
+        .map_err(|x| {
+            let err = format!("Error while editing a comment: {x:?}");
+            tracing::error!("{err}");
+            ServerFnError::new("Could not edit the comment, try again later")
+        })?;
+    if !edited {
+        return Err(ServerFnError::new("The comment can not be edited anymore"));
+    }
+    Ok(())
+}
+
+#[tracing::instrument]
+#[post("/api/get_comments", header: TypedHeader<Cookie>)]
 pub async fn get_comments(slug: String) -> Result<Vec<crate::models::Comment>, ServerFnError> {
+    let slug = visible_slug(slug, crate::auth::get_username_from_cookie(header).await).await?;
     crate::models::Comment::get_all(slug).await.map_err(|x| {
         let err = format!("Error while getting comments: {x:?}");
         tracing::error!("{err}");
@@ -62,7 +127,7 @@ pub async fn get_comments(slug: String) -> Result<Vec<crate::models::Comment>, S
 #[tracing::instrument]
 #[post("/api/delete_comment", header: TypedHeader<Cookie>)]
 pub async fn delete_comment(id: i32) -> Result<(), ServerFnError> {
-    let Some(logged_user) = crate::auth::get_username_from_cookie(header) else {
+    let Some(logged_user) = crate::auth::get_username_from_cookie(header).await else {
         return Err(ServerFnError::ServerError {
             message: "you must be logged in".to_string(),
             code: 401,
@@ -75,7 +140,6 @@ pub async fn delete_comment(id: i32) -> Result<(), ServerFnError> {
 
     crate::models::Comment::delete(id, logged_user)
         .await
-        .map(|_| ())
         .map_err(|x| {
             let err = format!("Error while posting a comment: {x:?}");
             tracing::error!("{err}");
@@ -96,6 +160,16 @@ pub fn Article(slug: ReadSignal<String>) -> Element {
         let value = slug();
         async move { get_article(value).await }
     });
+    // an old slug of a renamed article, show it under the current one
+    use_effect(move || {
+        if let Some(Ok(article_detail)) = &*article_resource.read() {
+            if article_detail.article.slug != slug() {
+                navigator().replace(crate::Route::Article {
+                    slug: article_detail.article.slug.clone(),
+                });
+            }
+        }
+    });
     // .suspend()?;
     rsx! {
         match &*article_resource.read() {
@@ -126,7 +200,10 @@ pub fn Article(slug: ReadSignal<String>) -> Element {
                                 }
                             }
                             div { class: "mb-5",
-                                p { class: "text-neutral-800 dark:text-gray-300", {article_detail.article.body.clone()} }
+                                div {
+                                    class: "markdown-body text-neutral-800 dark:text-gray-300",
+                                    dangerous_inner_html: article_detail.article.body_html.clone().unwrap_or_default(),
+                                }
                             }
                         }
                         div { class: "mb-5 px-1 py-1",
@@ -207,7 +284,7 @@ pub fn CommentSection(
             _ => String::new(),
         };
 
-        let res = post_comment(slug, body).await;
+        let res = post_comment(slug, body, None).await;
 
         match res {
             Ok(_) => {
@@ -221,18 +298,6 @@ pub fn CommentSection(
         }
     };
 
-    let on_submit_delete = move |evt: FormEvent| async move {
-        evt.prevent_default();
-        let id_data = evt.values().into_iter().filter(|d| d.0 == "id").last();
-        let id = match id_data {
-            Some((_, FormValue::Text(id))) => id,
-            _ => String::new(),
-        };
-        let _ = delete_comment(id.parse::<i32>().unwrap()).await;
-        comments_fut.restart();
-        article_resource.restart();
-    };
-
     rsx! {
         div { class: "mb-1",
             form { onsubmit: on_submit,
@@ -271,30 +336,225 @@ pub fn CommentSection(
                 }
             }
 
-            for comment in comments_result.iter() {
+            for comment in comments_result.iter().filter(|x| x.parent.is_none()) {
+                CommentThread {
+                    key: "{comment.id}",
+                    comment: comment.clone(),
+                    comments: comments_result,
+                    article_detail,
+                    on_change: move |_| {
+                        comments_fut.restart();
+                        article_resource.restart();
+                    },
+                }
+            }
+        }
+    }
+}
+
+/// A comment with its replies below it, which can be collapsed.
+#[component]
+fn CommentThread(
+    comment: crate::models::Comment,
+    comments: ReadSignal<Vec<crate::models::Comment>>,
+    article_detail: ReadSignal<ArticleDetailed>,
+    on_change: EventHandler,
+) -> Element {
+    let mut collapsed = use_signal(|| false);
+    let mut replying = use_signal(|| false);
+    let mut reply_data = use_signal(String::new);
+    let mut reply_status = use_signal(String::new);
+    let mut editing = use_signal(|| false);
+    let mut edit_data = use_signal(String::new);
+    let mut edit_status = use_signal(String::new);
+    let id = comment.id;
+    let is_author = comment.username == article_detail().logged_user.unwrap_or_default().username();
+    let replies = comments
+        .read()
+        .iter()
+        .filter(|x| x.parent == Some(id))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let on_submit_reply = move |evt: FormEvent| async move {
+        evt.prevent_default();
+        match post_comment(article_detail().article.slug, reply_data(), Some(id)).await {
+            Ok(_) => {
+                reply_data.set(String::new());
+                reply_status.set(String::new());
+                replying.set(false);
+                collapsed.set(false);
+                on_change.call(());
+            }
+            Err(e) => {
+                reply_status.set(format!("Status: {}", e));
+            }
+        }
+    };
+
+    let on_submit_edit = move |evt: FormEvent| async move {
+        evt.prevent_default();
+        match edit_comment(id, edit_data()).await {
+            Ok(_) => {
+                edit_status.set(String::new());
+                editing.set(false);
+                on_change.call(());
+            }
+            Err(e) => {
+                edit_status.set(format!("Status: {}", e));
+            }
+        }
+    };
 
-                div { class: "py-5",
-                    CommentUserIcon { comment: comment.clone() }
-                    div { class: "flex grow justify-between",
-                        p { class: "text-neutral-800 dark:text-gray-300", {comment.body.clone()} }
-                        div { class: "flex-none px-3 text-gray-600 dark:text-gray-400",
+    let on_submit_delete = move |evt: FormEvent| async move {
+        evt.prevent_default();
+        let id_data = evt.values().into_iter().filter(|d| d.0 == "id").last();
+        let id = match id_data {
+            Some((_, FormValue::Text(id))) => id,
+            _ => String::new(),
+        };
+        let _ = delete_comment(id.parse::<i32>().unwrap()).await;
+        on_change.call(());
+    };
 
-                            div {
-                                i { class: "fa-solid fa-calendar w-4 h-4" }
-                                span { class: "px-1", {comment.created_at.clone()} }
+    rsx! {
+        div { class: "py-5",
+            if comment.deleted {
+                p { class: "text-gray-500", "[deleted]" }
+            } else {
+                CommentUserIcon { comment: comment.clone() }
+                div { class: "flex grow justify-between",
+                    if editing() {
+                        form { class: "w-full", onsubmit: on_submit_edit,
+                            textarea {
+                                class: "focus:shadow-outline w-full border-b dark:border-gray-600 appearance-none rounded px-3 py-2 leading-tight text-sm text-gray-700 dark:text-gray-200 dark:bg-gray-700 shadow focus:outline-none",
+                                name: "body",
+                                oninput: move |evt| edit_data.set(evt.value()),
+                                value: "{edit_data}",
                             }
+                            div { class: "flex gap-2",
+                                button {
+                                    disabled: edit_data().len() < 3,
+                                    class: format!(
+                                        "rounded px-1 py-1 text-sm font-medium text-white {}",
+                                        if edit_data().len() < 3 {
+                                            "bg-gray-300 cursor-not-allowed"
+                                        } else {
+                                            "bg-blue-700 hover:bg-blue-800"
+                                        },
+                                    ),
+                                    "Save"
+                                }
+                                button {
+                                    r#type: "button",
+                                    class: "text-sm cursor-pointer text-gray-600 dark:text-gray-400",
+                                    onclick: move |_| editing.set(false),
+                                    "Cancel"
+                                }
+                            }
+                            p { class: "text-red-400", {edit_status()} }
+                        }
+                    } else {
+                        div {
+                            class: "markdown-body text-neutral-800 dark:text-gray-300",
+                            dangerous_inner_html: comment.body_html.clone().unwrap_or_default(),
+                        }
+                    }
+                    div { class: "flex-none px-3 text-gray-600 dark:text-gray-400",
 
-                           if {comment.username.clone()} == {article_detail().logged_user.unwrap_or_default().username()} {
-                            form {
-                                onsubmit: on_submit_delete,
+                        div {
+                            i { class: "fa-solid fa-calendar w-4 h-4" }
+                            span { class: "px-1", {comment.created_at.clone()} }
+                        }
+                        if let Some(edited_at) = comment.edited_at.clone() {
+                            div { class: "text-xs", "edited {edited_at}" }
+                        }
+
+                        if is_author {
+                            form { onsubmit: on_submit_delete,
                                 input { r#type: "hidden", name: "id", value: comment.id }
-                                button { class:"text-red-400 hover:rounded hover:border hover:bg-red-100", i { class:"fas fa-trash" } span { class:"px-1", "Delete" } }
+                                button { class: "text-red-400 hover:rounded hover:border hover:bg-red-100",
+                                    i { class: "fas fa-trash" }
+                                    span { class: "px-1", "Delete" }
+                                }
                             }
-                           }
                         }
                     }
                 }
             }
+            div { class: "flex gap-2 mt-1 text-sm text-gray-600 dark:text-gray-400",
+                if !replies.is_empty() {
+                    button {
+                        r#type: "button",
+                        class: "cursor-pointer",
+                        onclick: move |_| collapsed.set(!collapsed()),
+                        if collapsed() {
+                            "[+] show {replies.len()} replies"
+                        } else {
+                            "[-] hide replies"
+                        }
+                    }
+                }
+                if article_detail().logged_user.is_some() && !comment.deleted {
+                    button {
+                        r#type: "button",
+                        class: "cursor-pointer",
+                        onclick: move |_| replying.set(!replying()),
+                        "Reply"
+                    }
+                }
+                if is_author && comment.editable && !editing() {
+                    button {
+                        r#type: "button",
+                        class: "cursor-pointer",
+                        onclick: {
+                            let body = comment.body.clone();
+                            move |_| {
+                                edit_data.set(body.clone());
+                                editing.set(true);
+                            }
+                        },
+                        "Edit"
+                    }
+                }
+            }
+            if replying() {
+                form { class: "mt-1", onsubmit: on_submit_reply,
+                    textarea {
+                        class: "focus:shadow-outline w-full border-b dark:border-gray-600 appearance-none rounded px-3 py-2 leading-tight text-sm text-gray-700 dark:text-gray-200 dark:bg-gray-700 shadow focus:outline-none",
+                        name: "body",
+                        placeholder: "Write a reply...(min length 3 char)",
+                        oninput: move |evt| reply_data.set(evt.value()),
+                        value: "{reply_data}",
+                    }
+                    button {
+                        disabled: reply_data().len() < 3,
+                        class: format!(
+                            "rounded px-1 py-1 text-sm font-medium text-white {}",
+                            if reply_data().len() < 3 {
+                                "bg-gray-300 cursor-not-allowed"
+                            } else {
+                                "bg-blue-700 hover:bg-blue-800"
+                            },
+                        ),
+                        "Post Reply"
+                    }
+                    p { class: "text-red-400", {reply_status()} }
+                }
+            }
+        }
+        if !collapsed() && !replies.is_empty() {
+            div { class: "ml-2 pl-3 border-l dark:border-gray-600",
+                for reply in replies {
+                    CommentThread {
+                        key: "{reply.id}",
+                        comment: reply,
+                        comments,
+                        article_detail,
+                        on_change,
+                    }
+                }
+            }
         }
     }
 }
@@ -0,0 +1,263 @@
+use crate::models::{DiffLine, Revision};
+#[cfg(feature = "server")]
+use dioxus::fullstack::{Cookie, TypedHeader};
+use dioxus::prelude::*;
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Default, PartialEq)]
+pub struct RevisionHistory {
+    pub title: String,
+    pub revisions: Vec<Revision>,
+    /// Only the author can bring an older revision back.
+    pub can_restore: bool,
+}
+
+/// The article as the logged user is allowed to see it, drafts stay private to their author.
+#[cfg(feature = "server")]
+async fn visible_article(
+    slug: String,
+    logged_user: Option<String>,
+) -> Result<crate::models::Article, ServerFnError> {
+    crate::models::Article::for_article(slug, logged_user)
+        .await
+        .map_err(|x| {
+            let err = format!("Error while getting the article for its history: {x:?}");
+            tracing::error!("{err}");
+            ServerFnError::new("Could not retrieve the article, try again later")
+        })
+}
+
+#[tracing::instrument]
+#[post("/api/article_history", header: TypedHeader<Cookie>)]
+pub async fn article_history(slug: String) -> Result<RevisionHistory, ServerFnError> {
+    let logged_user = crate::auth::get_username_from_cookie(header).await;
+    let article = visible_article(slug, logged_user.clone()).await?;
+    let revisions = Revision::for_article(&article.slug).await.map_err(|x| {
+        let err = format!("Error while getting revisions: {x:?}");
+        tracing::error!("{err}");
+        ServerFnError::new("Could not retrieve the history, try again later")
+    })?;
+    Ok(RevisionHistory {
+        can_restore: logged_user.as_deref() == Some(article.author.username.as_str()),
+        title: article.title,
+        revisions,
+    })
+}
+
+#[tracing::instrument]
+#[post("/api/revision_diff", header: TypedHeader<Cookie>)]
+pub async fn revision_diff(
+    slug: String,
+    from: i64,
+    to: i64,
+) -> Result<Vec<DiffLine>, ServerFnError> {
+    let article =
+        visible_article(slug, crate::auth::get_username_from_cookie(header).await).await?;
+    let (from, to) = match (
+        Revision::get(&article.slug, from).await,
+        Revision::get(&article.slug, to).await,
+    ) {
+        (Ok(from), Ok(to)) => (from, to),
+        (Err(x), _) | (_, Err(x)) => {
+            let err = format!("Error while getting revisions to compare: {x:?}");
+            tracing::error!("{err}");
+            return Err(ServerFnError::new("Could not compare the revisions"));
+        }
+    };
+    from.diff(&to)
+        .ok_or_else(|| ServerFnError::new("These revisions differ in too many lines to compare"))
+}
+
+/// Saves the content of an older revision again, which makes it the newest one.
+#[tracing::instrument]
+#[post("/api/restore_revision", header: TypedHeader<Cookie>)]
+pub async fn restore_revision(slug: String, id: i64) -> Result<(), ServerFnError> {
+    let Some(logged_user) = crate::auth::get_username_from_cookie(header).await else {
+        return Err(ServerFnError::ServerError {
+            message: "you must be logged in".to_string(),
+            code: 401,
+            details: serde_json::json!(format!(
+                "Unauthorized: you must be logged to do this change"
+            ))
+            .into(),
+        });
+    };
+    let slug = visible_article(slug, Some(logged_user.clone())).await?.slug;
+    let revision = Revision::get(&slug, id).await.map_err(|x| {
+        let err = format!("Error while getting a revision to restore: {x:?}");
+        tracing::error!("{err}");
+        ServerFnError::new("Could not find that revision")
+    })?;
+    let article = super::validate_article(
+        revision.title,
+        revision.description,
+        revision.body.unwrap_or_default(),
+        revision.tag_list.join(" "),
+    )
+    .map_err(ServerFnError::new)?;
+    // only matches a row when the logged user wrote the article
+    super::update_article(logged_user, slug, article, None, None, None)
+        .await
+        .map(|_| ())
+        .map_err(|x| {
+            let err = format!("Error while restoring a revision: {x:?}");
+            tracing::error!("{err}");
+            ServerFnError::new("Could not restore the revision")
+        })
+}
+
+#[component]
+pub fn ArticleHistory(slug: ReadSignal<String>) -> Element {
+    let mut from = use_signal(|| None::<i64>);
+    let mut to = use_signal(|| None::<i64>);
+    let mut restore_status = use_signal(String::new);
+
+    let mut history_resource = use_resource(move || async move {
+        let res = article_history(slug()).await;
+        if let Ok(history) = &res {
+            // newest against the one before it, until the user picks others
+            if to.peek().is_none() {
+                to.set(history.revisions.first().map(|x| x.id));
+            }
+            if from.peek().is_none() {
+                from.set(
+                    history
+                        .revisions
+                        .get(1)
+                        .or(history.revisions.first())
+                        .map(|x| x.id),
+                );
+            }
+        }
+        res
+    });
+
+    let diff_resource = use_resource(move || async move {
+        match (from(), to()) {
+            (Some(from), Some(to)) => revision_diff(slug(), from, to).await.map(Some),
+            _ => Ok(None),
+        }
+    });
+
+    let restore = move |id: i64| async move {
+        match restore_revision(slug(), id).await {
+            Ok(_) => {
+                restore_status.set(format!("Restored revision #{id}"));
+                from.set(None);
+                to.set(None);
+                history_resource.restart();
+            }
+            Err(e) => restore_status.set(e.to_string()),
+        }
+    };
+
+    rsx! {
+        match &*history_resource.read() {
+            Some(Ok(history)) => rsx! {
+                document::Title { "History of {history.title}" }
+                div { class: "bg-opacity-60 inset-0 z-50 flex items-center justify-center",
+                    div { class: "z-70 block w-4/5 rounded-lg bg-white dark:bg-gray-800 p-4 text-gray-800 dark:text-gray-200",
+                        div { class: "flex justify-between mb-5",
+                            h1 { class: "text-xl leading-tight font-medium",
+                                "History of "
+                                {history.title.clone()}
+                            }
+                            a {
+                                class: "text-blue-500 underline",
+                                href: "/article/{slug}",
+                                "Back to the article"
+                            }
+                        }
+                        div { class: "mb-5",
+                            for (i , revision) in history.revisions.iter().enumerate() {
+                                RevisionRow {
+                                    revision: revision.clone(),
+                                    latest: i == 0,
+                                    from: from() == Some(revision.id),
+                                    to: to() == Some(revision.id),
+                                    can_restore: history.can_restore,
+                                    on_from: move |id| from.set(Some(id)),
+                                    on_to: move |id| to.set(Some(id)),
+                                    on_restore: move |id| async move { restore(id).await },
+                                }
+                            }
+                        }
+                        div { class: "text-red-600", {restore_status()} }
+                        match &*diff_resource.read() {
+                            Some(Ok(Some(lines))) => rsx! {
+                                div { class: "revision-diff",
+                                    for line in lines.iter() {
+                                        match line {
+                                            DiffLine::Same(text) => rsx! {
+                                                div { "  {text}" }
+                                            },
+                                            DiffLine::Removed(text) => rsx! {
+                                                div { class: "diff-removed", "- {text}" }
+                                            },
+                                            DiffLine::Added(text) => rsx! {
+                                                div { class: "diff-added", "+ {text}" }
+                                            },
+                                        }
+                                    }
+                                }
+                            },
+                            Some(Ok(None)) | None => rsx! {},
+                            Some(Err(e)) => rsx! {
+                                div { class: "text-red-600", "Failed to compare: {e}" }
+                            },
+                        }
+                    }
+                }
+            },
+            Some(Err(e)) => rsx! {
+                div { class: "text-gray-800 dark:text-gray-200", "Failed to load: {e}" }
+            },
+            None => rsx! {
+                div { class: "text-gray-800 dark:text-gray-200", "Loading history..." }
+            },
+        }
+    }
+}
+
+#[component]
+fn RevisionRow(
+    revision: Revision,
+    latest: bool,
+    from: bool,
+    to: bool,
+    can_restore: bool,
+    on_from: EventHandler<i64>,
+    on_to: EventHandler<i64>,
+    on_restore: EventHandler<i64>,
+) -> Element {
+    let id = revision.id;
+    rsx! {
+        div { class: "flex items-center gap-4 border-b py-1",
+            span { class: "font-bold", "#{id}" }
+            span { {revision.created_at.clone()} }
+            span { "by " {revision.author.clone()} }
+            span { {revision.title.clone()} }
+            button {
+                r#type: "button",
+                class: format!("px-2 {}", if from { "border-b-8" } else { "cursor-pointer" }),
+                onclick: move |_| on_from.call(id),
+                "From"
+            }
+            button {
+                r#type: "button",
+                class: format!("px-2 {}", if to { "border-b-8" } else { "cursor-pointer" }),
+                onclick: move |_| on_to.call(id),
+                "To"
+            }
+            if latest {
+                span { class: "text-xs", "current" }
+            } else if can_restore {
+                button {
+                    r#type: "button",
+                    class: "text-blue-500 underline cursor-pointer",
+                    onclick: move |_| on_restore.call(id),
+                    "Restore"
+                }
+            }
+        }
+    }
+}
@@ -9,10 +9,37 @@ pub enum EditorResponse {
     UpdateError,
     AuthError(String),
     Successful(String),
+    /// The article was saved by someone else since the editor loaded it, this is their copy.
+    Conflict(Box<crate::models::Article>),
 }
+
 #[cfg(feature = "server")]
 #[derive(Debug)]
-struct ArticleUpdate {
+pub(crate) enum SaveError {
+    /// The article is no longer at the version the save was based on.
+    Conflict,
+    Database(sqlx::Error),
+}
+
+#[cfg(feature = "server")]
+impl From<sqlx::Error> for SaveError {
+    fn from(err: sqlx::Error) -> Self {
+        Self::Database(err)
+    }
+}
+
+#[cfg(feature = "server")]
+impl std::fmt::Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Conflict => write!(f, "the article was changed in the meantime"),
+            Self::Database(err) => write!(f, "{err}"),
+        }
+    }
+}
+#[cfg(feature = "server")]
+#[derive(Debug)]
+pub(crate) struct ArticleUpdate {
     title: String,
     description: String,
     body: String,
@@ -22,10 +49,14 @@ struct ArticleUpdate {
 const TITLE_MIN_LENGTH: usize = 4;
 const DESCRIPTION_MIN_LENGTH: usize = 4;
 const BODY_MIN_LENGTH: usize = 10;
+/// Every save is kept as a revision and can be diffed, so bodies stay within bounds.
+const BODY_MAX_LENGTH: usize = 100_000;
+#[cfg(feature = "server")]
+const BODY_MAX_LINES: usize = 5_000;
 
 #[cfg(feature = "server")]
 #[tracing::instrument]
-fn validate_article(
+pub(crate) fn validate_article(
     title: String,
     description: String,
     body: String,
@@ -43,6 +74,18 @@ fn validate_article(
         return Err("You need to provide a body with at least 10 characters".into());
     }
 
+    if body.chars().count() > BODY_MAX_LENGTH {
+        return Err(format!(
+            "The body can't be longer than {BODY_MAX_LENGTH} characters"
+        ));
+    }
+
+    if body.lines().count() > BODY_MAX_LINES {
+        return Err(format!(
+            "The body can't have more than {BODY_MAX_LINES} lines"
+        ));
+    }
+
     let tag_list = tag_list
         .trim()
         .split_ascii_whitespace()
@@ -57,49 +100,199 @@ fn validate_article(
     })
 }
 
+/// Format of the `datetime-local` publish date input, taken as UTC.
+#[cfg(feature = "server")]
+const PUBLISH_AT_FORMAT: &str = "%Y-%m-%dT%H:%M";
+
+/// The publish date of the editor form, `None` when left empty.
+#[cfg(feature = "server")]
+fn parse_publish_at(
+    publish_at: &str,
+) -> Result<Option<sqlx::types::chrono::NaiveDateTime>, String> {
+    use sqlx::types::chrono::NaiveDateTime;
+    let publish_at = publish_at.trim();
+    if publish_at.is_empty() {
+        return Ok(None);
+    }
+    NaiveDateTime::parse_from_str(publish_at, PUBLISH_AT_FORMAT)
+        .or_else(|_| NaiveDateTime::parse_from_str(publish_at, "%Y-%m-%dT%H:%M:%S"))
+        .map(Some)
+        .map_err(|_| "You need to provide a valid publish date".to_string())
+}
+
+/// `published_at` of the article model, shown the way the publish date input expects it.
+fn publish_at_input(published_at: &str) -> String {
+    // DATE_FORMAT is "%d/%m/%Y %H:%M"
+    match published_at.split_once(' ') {
+        Some((date, time)) => {
+            let date = date.split('/').rev().collect::<Vec<_>>().join("-");
+            format!("{date}T{time}")
+        }
+        None => String::new(),
+    }
+}
+
+/// A free slug for `title`. `current` is the slug of the article being saved, empty for a
+/// new one, it is kept when the title still leads to it. Titles without anything ASCII fall
+/// back to a uuid.
+#[cfg(feature = "server")]
+async fn unique_slug(
+    conn: &mut sqlx::SqliteConnection,
+    title: &str,
+    current: &str,
+) -> Result<String, sqlx::Error> {
+    let base = crate::slug::slugify(title);
+    if base.is_empty() {
+        return Ok(if current.is_empty() {
+            uuid::Uuid::now_v7().to_string()
+        } else {
+            current.to_string()
+        });
+    }
+    if !current.is_empty() && crate::slug::derives_from(current, &base) {
+        return Ok(current.to_string());
+    }
+    for candidate in crate::slug::candidates(&base) {
+        if crate::models::Article::slug_available(&mut *conn, &candidate, current).await? {
+            return Ok(candidate);
+        }
+    }
+    unreachable!("slug candidates never run out")
+}
+
+/// Saves the article, `status` left out keeps the current one and publishes new articles.
+/// `publish_at` is only used for scheduled articles. A new title gets a new slug, the old
+/// one keeps redirecting to it. With `expected_version` the save only goes through when
+/// nobody saved the article since that version was loaded.
 #[cfg(feature = "server")]
 #[tracing::instrument]
-async fn update_article(
+pub(crate) async fn update_article(
     author: String,
     slug: String,
     article: ArticleUpdate,
-) -> Result<String, sqlx::Error> {
+    status: Option<crate::models::ArticleStatus>,
+    publish_at: Option<sqlx::types::chrono::NaiveDateTime>,
+    expected_version: Option<i64>,
+) -> Result<String, SaveError> {
     static BIND_LIMIT: usize = 65535;
+    let status = status.map(crate::models::ArticleStatus::as_str);
+    // the format of CURRENT_TIMESTAMP, so the scheduler can compare them
+    let publish_at = publish_at.map(|x| x.format("%Y-%m-%d %H:%M:%S").to_string());
     let mut transaction = crate::database::server::get_db().begin().await?;
-    let (rows_affected, slug) = if !slug.is_empty() {
-        (
+    let (rows_affected, slug, goes_live) = if !slug.is_empty() {
+        // the article may have been renamed since `slug` was loaded, old slugs lead to it
+        let Some(current) = sqlx::query!(
+            r#"SELECT slug as "slug!", title, version, status FROM Articles
+            WHERE author=$2
+                and (slug=$1 or slug=(SELECT article FROM ArticleSlugs WHERE old_slug=$1))
+            ORDER BY slug=$1 DESC
+            LIMIT 1"#,
+            slug,
+            author
+        )
+        .fetch_optional(transaction.as_mut())
+        .await?
+        else {
+            tracing::error!("no article {slug} of {author}");
+            return Err(sqlx::Error::RowNotFound.into());
+        };
+        if expected_version.is_some_and(|x| x != current.version) {
+            return Err(SaveError::Conflict);
+        }
+        let slug = current.slug;
+        let was_published = current.status == crate::models::ArticleStatus::Published.as_str();
+        let new_slug = if current.title == article.title {
+            slug.clone()
+        } else {
+            unique_slug(transaction.as_mut(), &article.title, &slug).await?
+        };
+        let rows_affected = sqlx::query!(
+            "
+UPDATE Articles SET
+    title=$1, description=$2, body=$3, status=coalesce($6, status), updated_at=CURRENT_TIMESTAMP,
+    published_at = CASE coalesce($6, status)
+        WHEN 'draft' THEN NULL
+        WHEN 'scheduled' THEN coalesce($7, published_at)
+        ELSE CASE WHEN status = 'published' THEN published_at ELSE CURRENT_TIMESTAMP END
+    END,
+    slug=$8,
+    version=version + 1
+WHERE slug=$4 and author=$5 and version=$9",
+            article.title,
+            article.description,
+            article.body,
+            slug,
+            author,
+            status,
+            publish_at,
+            new_slug,
+            current.version,
+        )
+        .execute(transaction.as_mut())
+        .await?
+        .rows_affected();
+        if rows_affected == 0 {
+            // saved by someone else between the select and the update
+            return Err(SaveError::Conflict);
+        }
+        if new_slug != slug {
+            // the rename cascaded to the older entries, they point at new_slug already
             sqlx::query!(
-                "UPDATE Articles SET title=$1, description=$2, body=$3 WHERE slug=$4 and author=$5",
-                article.title,
-                article.description,
-                article.body,
+                "INSERT INTO ArticleSlugs(old_slug, article) VALUES ($1, $2)
+                ON CONFLICT(old_slug) DO UPDATE SET article=excluded.article, created_at=CURRENT_TIMESTAMP",
                 slug,
-                author,
+                new_slug
             )
             .execute(transaction.as_mut())
-            .await?
-            .rows_affected(),
-            slug.to_string(),
-        )
+            .await?;
+            sqlx::query!("DELETE FROM ArticleSlugs WHERE old_slug=$1", new_slug)
+                .execute(transaction.as_mut())
+                .await?;
+        }
+        let goes_live =
+            !was_published && status == Some(crate::models::ArticleStatus::Published.as_str());
+        (rows_affected, new_slug, goes_live)
     } else {
-        let slug = uuid::Uuid::now_v7().to_string();
+        let slug = unique_slug(transaction.as_mut(), &article.title, "").await?;
+        let status = status.unwrap_or(crate::models::ArticleStatus::Published.as_str());
         (sqlx::query!(
-            "INSERT INTO Articles(slug, title, description, body, author) VALUES ($1, $2, $3, $4, $5)",
+            "
+INSERT INTO Articles(slug, title, description, body, author, status, published_at)
+VALUES ($1, $2, $3, $4, $5, $6, CASE $6 WHEN 'published' THEN CURRENT_TIMESTAMP WHEN 'scheduled' THEN $7 END)",
             slug,
             article.title,
             article.description,
             article.body,
-            author
+            author,
+            status,
+            publish_at
         )
         .execute(transaction.as_mut())
         .await?.rows_affected(),
-        slug)
+        slug,
+        status == crate::models::ArticleStatus::Published.as_str())
     };
     if rows_affected != 1 {
         // We are going to modify just one row, otherwise something funky is going on
         tracing::error!("no rows affected");
-        return Err(sqlx::Error::RowNotFound);
+        return Err(sqlx::Error::RowNotFound.into());
     }
+    let mut tags = article
+        .tag_list
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    tags.sort_unstable();
+    crate::models::Revision::insert(
+        transaction.as_mut(),
+        &slug,
+        &author,
+        &article.title,
+        &article.description,
+        &article.body,
+        &tags.join(" "),
+    )
+    .await?;
     sqlx::query!("DELETE FROM ArticleTags WHERE article=$1", slug)
         .execute(transaction.as_mut())
         .await?;
@@ -114,10 +307,17 @@ async fn update_article(
         qb.build().execute(transaction.as_mut()).await?;
     }
 
+    if goes_live {
+        crate::models::notify_followers(transaction.as_mut(), &author, &slug).await?;
+    }
+
     transaction.commit().await?;
     Ok(slug)
 }
 
+/// `publish_at` is `None` to save a draft, empty to publish right away or the date to
+/// schedule the article for.
+#[allow(clippy::too_many_arguments)]
 #[tracing::instrument]
 #[post("/api/editor_action", header: TypedHeader<Cookie>)]
 pub async fn editor_action(
@@ -126,8 +326,10 @@ pub async fn editor_action(
     body: String,
     tag_list: String,
     slug: String,
+    publish_at: Option<String>,
+    version: i64,
 ) -> Result<EditorResponse, ServerFnError> {
-    let Some(author) = crate::auth::get_username_from_cookie(header) else {
+    let Some(author) = crate::auth::get_username_from_cookie(header).await else {
         return Ok(EditorResponse::AuthError(
             "you should be authenticated".to_string(),
         ));
@@ -136,13 +338,46 @@ pub async fn editor_action(
         Ok(x) => x,
         Err(x) => return Ok(EditorResponse::ValidationError(x)),
     };
-    match update_article(author, slug, article).await {
+    let publish = publish_at.is_some();
+    let publish_at = match parse_publish_at(publish_at.as_deref().unwrap_or_default()) {
+        Ok(x) => x,
+        Err(x) => return Ok(EditorResponse::ValidationError(x)),
+    };
+    let (status, publish_at) = match publish_at {
+        _ if !publish => (crate::models::ArticleStatus::Draft, None),
+        // a date in the past publishes right away
+        Some(at) if at > sqlx::types::chrono::Utc::now().naive_utc() => {
+            (crate::models::ArticleStatus::Scheduled, Some(at))
+        }
+        _ => (crate::models::ArticleStatus::Published, None),
+    };
+    // new articles have no version to compare with
+    let expected_version = (!slug.is_empty()).then_some(version);
+    match update_article(
+        author.clone(),
+        slug.clone(),
+        article,
+        Some(status),
+        publish_at,
+        expected_version,
+    )
+    .await
+    {
         Ok(x) => {
             // crate::server_fn::redirect::call_redirect_hook("/article/{x}");
             let _ = dioxus_fullstack::Redirect::to("/article/{x}");
 
             Ok(EditorResponse::Successful(x))
         }
+        Err(SaveError::Conflict) => {
+            match crate::models::Article::for_article(slug, Some(author)).await {
+                Ok(x) => Ok(EditorResponse::Conflict(Box::new(x))),
+                Err(x) => {
+                    tracing::error!("EDITOR ERROR: {}", x.to_string());
+                    Ok(EditorResponse::UpdateError)
+                }
+            }
+        }
         Err(x) => {
             tracing::error!("EDITOR ERROR: {}", x.to_string());
             Ok(EditorResponse::UpdateError)
@@ -155,6 +390,12 @@ pub fn Editor(slug: String) -> Element {
     let nav = navigator();
     let mut editor_status = use_signal(|| "".to_string());
     let mut edit_article = use_signal(|| super::article::ArticleDetailed::default());
+    // set by the button the form was submitted with
+    let mut publish = use_signal(|| true);
+    let mut publish_at = use_signal(String::new);
+    // the version the form is based on, and the newer copy a save ran into
+    let mut version = use_signal(|| 0);
+    let mut conflict = use_signal(|| None::<crate::models::Article>);
 
     if !slug.is_empty() {
         let _ = use_resource(move || {
@@ -162,6 +403,12 @@ pub fn Editor(slug: String) -> Element {
             async move {
                 match super::article::get_article(value).await {
                     Ok(res) => {
+                        if res.article.status == crate::models::ArticleStatus::Scheduled {
+                            publish_at.set(publish_at_input(
+                                res.article.published_at.as_deref().unwrap_or_default(),
+                            ));
+                        }
+                        version.set(res.article.version);
                         edit_article.set(res);
                     }
 
@@ -205,16 +452,37 @@ pub fn Editor(slug: String) -> Element {
             _ => String::new(),
         };
 
-        let res = editor_action(title, description, body, tags, edit_article().article.slug).await;
+        let res = editor_action(
+            title,
+            description,
+            body,
+            tags,
+            edit_article().article.slug,
+            publish().then_some(publish_at()),
+            version(),
+        )
+        .await;
 
         match res {
-            Ok(EditorResponse::Successful(_)) => {
+            Ok(EditorResponse::Successful(_)) if publish() && publish_at().is_empty() => {
                 nav.replace(crate::Route::Home {});
             }
+            Ok(EditorResponse::Successful(slug)) => {
+                nav.replace(crate::Route::Article { slug });
+            }
             Ok(EditorResponse::ValidationError(e)) => {
                 editor_status.set(e);
             }
-            Ok(EditorResponse::UpdateError) => (),
+            Ok(EditorResponse::Conflict(article)) => {
+                editor_status.set(
+                    "Someone saved this article while you were editing it, compare with their copy"
+                        .to_string(),
+                );
+                conflict.set(Some(*article));
+            }
+            Ok(EditorResponse::UpdateError) => {
+                editor_status.set("Could not save the article, try again later".to_string());
+            }
             Ok(EditorResponse::AuthError(_)) => {
                 nav.replace(crate::Route::Login {});
             }
@@ -227,8 +495,12 @@ pub fn Editor(slug: String) -> Element {
     rsx! {
         div { class: "fixed inset-0 z-50 flex items-center justify-center bg-gray-900 bg-opacity-60",
             document::Title { "Create a new Article" }
-            div { class: "block rounded-lg bg-white dark:bg-gray-800 w-2/5 p-4 shadow-[0_2px_15px_-3px_rgba(0,0,0,0.07),0_10px_20px_-2px_rgba(0,0,0,0.04)] z-70",
-                div { class: "col-md-10 offset-md-1 col-xs-12",
+            div {
+                class: format!(
+                    "block rounded-lg bg-white dark:bg-gray-800 {} p-4 shadow-[0_2px_15px_-3px_rgba(0,0,0,0.07),0_10px_20px_-2px_rgba(0,0,0,0.04)] z-70 flex gap-4",
+                    if conflict().is_some() { "w-4/5" } else { "w-2/5" },
+                ),
+                div { class: "col-md-10 offset-md-1 col-xs-12 w-full",
                     form { id: "editor", onsubmit: on_submit,
                         div { class: "mb-5",
                             input {
@@ -257,6 +529,7 @@ pub fn Editor(slug: String) -> Element {
                                 class: "shadow appearance-none border dark:border-gray-600 rounded w-full py-2 px-3 text-gray-700 dark:text-gray-200 dark:bg-gray-700 leading-tight focus:ring",
                                 placeholder: "Write your article (in markdown)",
                                 minlength: BODY_MIN_LENGTH,
+                                maxlength: BODY_MAX_LENGTH,
                                 value: edit_article().article.body,
                             }
                         }
@@ -269,11 +542,40 @@ pub fn Editor(slug: String) -> Element {
                                 value: edit_article().article.tag_list.join(" "),
                             }
                         }
+                        div { class: "mb-5",
+                            label { class: "block mb-2 text-gray-700 dark:text-gray-200",
+                                "Publish at (UTC), leave empty to publish right away"
+                            }
+                            input {
+                                class: "shadow appearance-none border dark:border-gray-600 rounded w-full py-2 px-3 text-gray-700 dark:text-gray-200 dark:bg-gray-700 leading-tight focus:ring",
+                                name: "publish_at",
+                                r#type: "datetime-local",
+                                value: publish_at(),
+                                oninput: move |evt| publish_at.set(evt.value()),
+                            }
+                        }
 
                         div { class: "flex flex-row-reverse space-x-4 space-x-reverse",
 
-                            button { class: "bg-blue-700 hover:bg-blue-800 px-5 py-3 text-white rounded-lg",
-                                "Publish Article"
+                            button {
+                                class: "bg-blue-700 hover:bg-blue-800 px-5 py-3 text-white rounded-lg",
+                                onclick: move |_| publish.set(true),
+                                if publish_at().is_empty() {
+                                    "Publish Article"
+                                } else {
+                                    "Schedule Article"
+                                }
+                            }
+                            button {
+                                class: "bg-gray-500 hover:bg-gray-600 px-5 py-3 text-white rounded-lg",
+                                onclick: move |_| publish.set(false),
+                                if edit_article().article.status != crate::models::ArticleStatus::Draft
+                                    && !edit_article().article.slug.is_empty()
+                                {
+                                    "Unpublish to Draft"
+                                } else {
+                                    "Save Draft"
+                                }
                             }
                             button {
                                 class: "bg-gray-300 hover:bg-gray-400 dark:bg-gray-600 dark:hover:bg-gray-500 px-5 py-3 text-white rounded-lg",
@@ -291,6 +593,60 @@ pub fn Editor(slug: String) -> Element {
                         div { class: "text-red-600", {editor_status()} }
                     }
                 }
+                if let Some(theirs) = conflict() {
+                    ConflictCopy {
+                        theirs,
+                        on_keep_mine: move |theirs: crate::models::Article| {
+                            version.set(theirs.version);
+                            conflict.set(None);
+                            editor_status.set(String::new());
+                        },
+                        on_take_theirs: move |theirs: crate::models::Article| {
+                            version.set(theirs.version);
+                            edit_article.write().article = theirs;
+                            conflict.set(None);
+                            editor_status.set(String::new());
+                        },
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The copy of the article that was saved while the user was editing, shown next to their
+/// unsaved text so they can pick one before saving again.
+#[component]
+fn ConflictCopy(
+    theirs: crate::models::Article,
+    on_keep_mine: EventHandler<crate::models::Article>,
+    on_take_theirs: EventHandler<crate::models::Article>,
+) -> Element {
+    let keep = theirs.clone();
+    let take = theirs.clone();
+    rsx! {
+        div { class: "w-full text-gray-700 dark:text-gray-200",
+            h2 { class: "text-xl leading-tight font-medium mb-2",
+                "Saved by someone else at "
+                {theirs.updated_at.clone()}
+            }
+            div { class: "mb-2 font-bold", {theirs.title.clone()} }
+            div { class: "mb-2", {theirs.description.clone()} }
+            div { class: "mb-2", "tags: " {theirs.tag_list.join(" ")} }
+            pre { class: "revision-diff mb-5", {theirs.body.clone()} }
+            div { class: "flex space-x-4",
+                button {
+                    class: "bg-blue-700 hover:bg-blue-800 px-5 py-3 text-white rounded-lg",
+                    r#type: "button",
+                    onclick: move |_| on_keep_mine.call(keep.clone()),
+                    "Keep my text"
+                }
+                button {
+                    class: "bg-gray-500 hover:bg-gray-600 px-5 py-3 text-white rounded-lg",
+                    r#type: "button",
+                    onclick: move |_| on_take_theirs.call(take.clone()),
+                    "Take theirs"
+                }
             }
         }
     }
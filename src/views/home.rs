@@ -158,12 +158,18 @@ pub async fn home_articles(
 ) -> Result<Vec<Article>, ServerFnError> {
     dioxus_logger::tracing::info!("Starting home_articles");
 
-    Ok(Article::for_home_page(page, amount, tag, my_feed, header)
-        .await
-        .map_err(|x| {
-            tracing::error!("problem while fetching home articles: {x:?}");
-            ServerFnError::new("Problem while fetching home articles")
-        })?)
+    Article::for_home_page(
+        page,
+        amount,
+        tag,
+        my_feed,
+        crate::auth::get_username_from_cookie(header).await,
+    )
+    .await
+    .map_err(|x| {
+        tracing::error!("problem while fetching home articles: {x:?}");
+        ServerFnError::new("Problem while fetching home articles")
+    })
 }
 
 #[component]
@@ -275,6 +281,8 @@ async fn get_tags() -> Result<Vec<String>, ServerFnError> {
                     Articles AS T2
                 ON
                     T1.article = T2.slug
+                WHERE
+                    T2.status = 'published'
                 GROUP BY
                     T1.tag
             	ORDER BY
@@ -299,6 +307,8 @@ async fn get_tags() -> Result<Vec<String>, ServerFnError> {
                     Articles AS T2
                 ON
                     T1.article = T2.slug
+                WHERE
+                    T2.status = 'published'
                 GROUP BY
                     T1.tag
                 ORDER BY
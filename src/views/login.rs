@@ -6,6 +6,8 @@ pub fn Login() -> Element {
     let nav = navigator();
     let mut passwd_visible = use_signal(|| false);
     let mut login_status = use_signal(|| String::new());
+    // the password was right, the login waits for the second factor
+    let mut mfa_pending = use_signal(|| false);
 
     let on_submit = move |evt: FormEvent| async move {
         evt.prevent_default();
@@ -35,8 +37,15 @@ pub fn Login() -> Element {
                 Ok(Some(user)) if user.username() == username => {
                     nav.replace(crate::Route::Home {});
                 }
+                Ok(None) => {
+                    login_status.set(String::new());
+                    mfa_pending.set(true);
+                }
                 _ => login_status.set("Login failed! : username or password incorrect".to_string()),
             },
+            Err(ServerFnError::ServerError {
+                code: 429, message, ..
+            }) => login_status.set(format!("Login failed! : {message}")),
             Err(err) => {
                 tracing::debug!("login failed! : {err}");
                 login_status.set("Login failed! : username or password incorrect".to_string())
@@ -44,6 +53,81 @@ pub fn Login() -> Element {
         }
     };
 
+    let on_submit_code = move |evt: FormEvent| async move {
+        evt.prevent_default();
+        let code = match evt.values().into_iter().filter(|d| d.0 == "code").last() {
+            Some((_, FormValue::Text(value))) => value,
+            _ => String::new(),
+        };
+        match auth::login_mfa(code).await {
+            Ok(_) => {
+                nav.replace(crate::Route::Home {});
+            }
+            Err(ServerFnError::ServerError {
+                code: 429, message, ..
+            }) => login_status.set(format!("Login failed! : {message}")),
+            Err(err) => {
+                tracing::debug!("second factor failed! : {err}");
+                login_status.set(
+                    "Login failed! : the code is not valid, or took too long to enter".to_string(),
+                )
+            }
+        }
+    };
+
+    if mfa_pending() {
+        return rsx! {
+            div { class: "fixed inset-0 z-50 flex items-center justify-center bg-gray-900 bg-opacity-60",
+                div { class: "block rounded-lg bg-white dark:bg-gray-800 w-2/5 p-4 shadow-[0_2px_15px_-3px_rgba(0,0,0,0.07),0_10px_20px_-2px_rgba(0,0,0,0.04)] z-70",
+                    h5 { class: "mb-5 text-xl font-medium leading-tight text-gray-800 dark:text-gray-200",
+                        " Enter the code from your authenticator app, or one of your recovery codes."
+                    }
+                    document::Title { "Login to MyApp" }
+                    form {
+                        class: "mb-10",
+                        id: "login_mfa",
+                        onsubmit: on_submit_code,
+
+                        label {
+                            class: "block text-gray-700 dark:text-gray-300 text-sm font-bold",
+                            r#for: "code",
+                            "Code"
+                        }
+
+                        div { class: "mb-5",
+                            input {
+                                class: "shadow appearance-none border dark:border-gray-600 rounded w-full py-2 px-3 text-gray-700 dark:text-gray-200 dark:bg-gray-700 leading-tight focus:outline-none focus:shadow-outline",
+                                id: "code",
+                                name: "code",
+                                r#type: "text",
+                                autocomplete: "one-time-code",
+                                placeholder: "123456",
+                            }
+                        }
+
+                        div { class: "flex flex-row-reverse space-x-4 space-x-reverse",
+                            button { class: "bg-blue-700 hover:bg-blue-800 px-5 py-2 text-white rounded-lg",
+                                "Verify"
+                            }
+                            button {
+                                class: "bg-gray-300 hover:bg-gray-400 dark:bg-gray-600 dark:hover:bg-gray-500 px-5 py-2 text-white rounded-lg",
+                                r#type: "button",
+                                onclick: move |_| {
+                                    login_status.set(String::new());
+                                    mfa_pending.set(false);
+                                },
+                                "Back"
+                            }
+                        }
+                        div {
+                            p { class: "block text-red-700 text-sm font-bold", {login_status()} }
+                        }
+                    }
+                }
+            }
+        };
+    }
+
     rsx! {
         div { class: "fixed inset-0 z-50 flex items-center justify-center bg-gray-900 bg-opacity-60",
             div { class: "block rounded-lg bg-white dark:bg-gray-800 w-2/5 p-4 shadow-[0_2px_15px_-3px_rgba(0,0,0,0.07),0_10px_20px_-2px_rgba(0,0,0,0.04)] z-70",
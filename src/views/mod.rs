@@ -6,10 +6,17 @@ pub use login::Login;
 
 mod editor;
 pub use editor::Editor;
+#[cfg(feature = "server")]
+pub(crate) use editor::{update_article, validate_article, SaveError};
 
 mod article;
+#[cfg(feature = "server")]
+pub(crate) use article::visible_slug;
 pub use article::{Article, ArticleDetailed};
 
+mod article_history;
+pub use article_history::ArticleHistory;
+
 mod user_profile;
 pub use user_profile::Profile;
 
@@ -19,8 +26,14 @@ pub use signup::SignUp;
 mod reset_password;
 pub use reset_password::ResetPasswd;
 
+mod verify_email;
+pub use verify_email::VerifyEmail;
+
 mod settings;
 pub use settings::Settings;
 
 mod search;
 pub use search::SearchResults;
+
+mod notifications;
+pub use notifications::{unread_notifications, Notifications};
@@ -0,0 +1,159 @@
+use crate::models::{NotificationGroup, NotificationKind};
+use crate::UnreadNotifications;
+#[cfg(feature = "server")]
+use dioxus::fullstack::{Cookie, TypedHeader};
+use dioxus::prelude::*;
+
+#[cfg(feature = "server")]
+async fn logged_user(header: TypedHeader<Cookie>) -> Result<String, ServerFnError> {
+    crate::auth::get_username_from_cookie(header)
+        .await
+        .ok_or_else(|| ServerFnError::ServerError {
+            message: "you must be logged in".to_string(),
+            code: 401,
+            details: serde_json::json!(format!(
+                "Unauthorized: you must be logged to see your notifications"
+            ))
+            .into(),
+        })
+}
+
+#[tracing::instrument]
+#[post("/api/notifications", header: TypedHeader<Cookie>)]
+pub async fn notifications() -> Result<Vec<NotificationGroup>, ServerFnError> {
+    let username = logged_user(header).await?;
+    NotificationGroup::for_user(&username).await.map_err(|x| {
+        let err = format!("Error while getting notifications: {x:?}");
+        tracing::error!("{err}");
+        ServerFnError::new("Could not retrieve notifications, try again later")
+    })
+}
+
+/// Unread notifications of the logged user, 0 for visitors.
+#[tracing::instrument]
+#[post("/api/unread_notifications", header: TypedHeader<Cookie>)]
+pub async fn unread_notifications() -> Result<i64, ServerFnError> {
+    let Some(username) = crate::auth::get_username_from_cookie(header).await else {
+        return Ok(0);
+    };
+    NotificationGroup::unread_count(&username)
+        .await
+        .map_err(|x| {
+            let err = format!("Error while counting notifications: {x:?}");
+            tracing::error!("{err}");
+            ServerFnError::new("Could not count notifications")
+        })
+}
+
+/// Marks the notifications with `ids` read, all of them when `ids` is empty. Returns how
+/// many are left unread.
+#[tracing::instrument]
+#[post("/api/mark_notifications_read", header: TypedHeader<Cookie>)]
+pub async fn mark_notifications_read(ids: Vec<i64>) -> Result<i64, ServerFnError> {
+    let username = logged_user(header).await?;
+    let ids = (!ids.is_empty()).then_some(ids.as_slice());
+    let map_err = |x: sqlx::Error| {
+        let err = format!("Error while marking notifications read: {x:?}");
+        tracing::error!("{err}");
+        ServerFnError::new("Could not update notifications, try again later")
+    };
+    NotificationGroup::mark_read(&username, ids)
+        .await
+        .map_err(map_err)?;
+    NotificationGroup::unread_count(&username)
+        .await
+        .map_err(map_err)
+}
+
+#[component]
+pub fn Notifications() -> Element {
+    let mut unread = use_context::<Signal<UnreadNotifications>>();
+    let mut status = use_signal(String::new);
+    let mut notifications_resource = use_resource(notifications);
+
+    let mark_read = move |ids: Vec<i64>| async move {
+        match mark_notifications_read(ids).await {
+            Ok(count) => {
+                unread.set(UnreadNotifications(count));
+                notifications_resource.restart();
+            }
+            Err(e) => status.set(e.to_string()),
+        }
+    };
+
+    rsx! {
+        document::Title { "Notifications" }
+        div { class: "flex items-center justify-center",
+            div { class: "z-70 block w-4/5 rounded-lg bg-white dark:bg-gray-800 p-4 text-gray-800 dark:text-gray-200",
+                div { class: "flex justify-between mb-5",
+                    h1 { class: "text-xl leading-tight font-medium", "Notifications" }
+                    button {
+                        r#type: "button",
+                        class: "text-blue-500 underline cursor-pointer",
+                        onclick: move |_| async move { mark_read(vec![]).await },
+                        "Mark all as read"
+                    }
+                }
+                div { class: "text-red-600", {status()} }
+                match &*notifications_resource.read() {
+                    Some(Ok(groups)) if groups.is_empty() => rsx! {
+                        div { "Nothing yet." }
+                    },
+                    Some(Ok(groups)) => rsx! {
+                        for group in groups.iter() {
+                            NotificationRow { key: "{group.ids[0]}", group: group.clone() }
+                        }
+                    },
+                    Some(Err(e)) => rsx! {
+                        div { "Failed to load: {e}" }
+                    },
+                    None => rsx! {
+                        div { "Loading notifications..." }
+                    },
+                }
+            }
+        }
+    }
+}
+
+#[component]
+fn NotificationRow(group: NotificationGroup) -> Element {
+    let nav = navigator();
+    // a follow leads to the newest follower, the others to the article
+    let target = match (&group.kind, &group.article) {
+        (NotificationKind::Follow, _) | (_, None) => crate::Route::Profile {
+            profile_user: group.actors.first().cloned().unwrap_or_default(),
+        },
+        (_, Some(slug)) => crate::Route::Article { slug: slug.clone() },
+    };
+    let ids = group.ids.clone();
+    let unread = group.unread;
+
+    rsx! {
+        div {
+            class: format!(
+                "flex justify-between border-b py-2 cursor-pointer {}",
+                if unread { "font-bold" } else { "" },
+            ),
+            // read before leaving, the page and its tasks go away with the navigation
+            onclick: move |_| {
+                let ids = ids.clone();
+                let target = target.clone();
+                async move {
+                    if unread {
+                        let _ = mark_notifications_read(ids).await;
+                    }
+                    nav.push(target);
+                }
+            },
+            span {
+                {group.text()}
+                if let Some(title) = &group.article_title {
+                    " "
+                    span { class: "text-blue-500", {title.clone()} }
+                }
+            }
+            span { class: "text-sm text-gray-600 dark:text-gray-400", {group.created_at.clone()} }
+        }
+    }
+}
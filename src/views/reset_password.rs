@@ -1,78 +1,88 @@
 use dioxus::{document, prelude::*};
-use std::env;
 
 use crate::{auth::logout, LoggedInUser};
 
+/// Reset requests allowed per hour for one email address and for one IP address. The IP is
+/// the one of [`crate::auth::ClientInfo`], forwarding headers only count from trusted proxies
+/// or the limit could be dodged by making them up.
 #[cfg(feature = "server")]
-#[allow(dead_code)]
-struct EmailCredentials {
-    email: String,
-    passwd: String,
-    smtp_server: String,
-}
+const RESET_REQUESTS_PER_EMAIL: i64 = 3;
+#[cfg(feature = "server")]
+const RESET_REQUESTS_PER_IP: i64 = 10;
+/// How long an emailed reset link stays usable, in seconds.
+#[cfg(feature = "server")]
+const RESET_TOKEN_TTL: i64 = 3_600;
 
+/// Same answer whether or not the email belongs to an account.
+const RESET_EMAIL_SENT: &str =
+    "Email sent if the address belongs to an account. Check email and click the reset url link inside.";
 #[cfg(feature = "server")]
-static EMAIL_CREDS: std::sync::OnceLock<EmailCredentials> = std::sync::OnceLock::new();
+const RESET_INVALID_LINK: &str =
+    "This reset link is invalid or has expired, please request a new one!";
 
 #[tracing::instrument]
-#[post("/api/reset_password_1", headers: dioxus::fullstack::HeaderMap)]
+#[post("/api/reset_password_1", headers: dioxus::fullstack::HeaderMap, peer: axum::extract::ConnectInfo<std::net::SocketAddr>)]
 pub async fn reset_password_1(email: String) -> Result<String, ServerFnError> {
-    if let Err(x) = crate::models::User::get_email(email.clone()).await {
-        let err = format!("Bad email ID: Provided email not found.");
-        tracing::error!("{err} {x:?} ");
-        return Err(ServerFnError::new(err));
-    } else {
-        let creds = EMAIL_CREDS.get_or_init(|| EmailCredentials {
-            email: env::var("MAILER_EMAIL").unwrap(),
-            passwd: env::var("MAILER_PASSWD").unwrap(),
-            smtp_server: env::var("MAILER_SMTP_SERVER").unwrap(),
-        });
-        // dioxus::logger::tracing::info!("the header is {headers:?}");
-        let host = headers.get("host").unwrap().to_str().unwrap();
-        let scheme = if cfg!(debug_assertions) {
-            "http"
-        } else {
-            "https"
-        };
-        let token = crate::auth::encode_token(crate::auth::TokenClaims {
-            sub: email.clone(),
-            exp: (sqlx::types::chrono::Utc::now().timestamp() as usize) + 3_600,
-        })
-        .unwrap();
-        let uri = format!(
-            "{}://{}/reset_password?token={}",
-            scheme,
-            host.to_owned(),
-            token
-        );
-        // Build a simple multipart message
-        let message = mail_send::mail_builder::MessageBuilder::new()
-            .from(("Realworld Dioxus", creds.email.as_str()))
-            .to(vec![("You", email.as_str())])
-            .subject("Your password reset from realworld leptos")
-            .text_body(format!(
-                "You can reset your password accessing the following link: {uri}"
-            ));
-
-        // Connect to the SMTP submissions port, upgrade to TLS and
-        // authenticate using the provided credentials.
-        dioxus::logger::tracing::info!("The email is {:?}", message);
+    let email = email.trim().to_string();
+    let client = crate::auth::ClientInfo::new(&headers, peer.0);
+    let (by_email, by_ip) =
+        crate::models::PasswordReset::record_request(&email.to_lowercase(), &client.ip)
+            .await
+            .map_err(|x| {
+                tracing::error!("problem while recording the reset request {x:?}");
+                ServerFnError::new("Unexpected error, please try later")
+            })?;
+    if by_email > RESET_REQUESTS_PER_EMAIL || by_ip > RESET_REQUESTS_PER_IP {
+        tracing::info!(email, ip = client.ip, "too many reset requests");
+        return Err(ServerFnError::new(
+            "Too many reset requests, please try again later.",
+        ));
+    }
 
-        // ********* UNCOMMENT IF NEEDED *********
-        // if smtp available, then uncomment below mail send part. Else use above logging to get a reset link to test
-        // Incorrect smtp may cause the thread to panic after multiple attempts
+    let user = match crate::models::User::get_email(email.clone()).await {
+        Ok(user) => user,
+        Err(x) => {
+            tracing::info!("reset requested for an unknown email {x:?}");
+            return Ok(RESET_EMAIL_SENT.to_string());
+        }
+    };
+    // nobody proved the address is theirs, it could belong to someone else
+    if !user.email_verified() {
+        tracing::info!(
+            "reset requested for the unconfirmed email of {}",
+            user.username()
+        );
+        return Ok(RESET_EMAIL_SENT.to_string());
+    }
 
-        // mail_send::SmtpClientBuilder::new(creds.smtp_server.as_str(), 587)
-        //     .implicit_tls(false)
-        //     .credentials((creds.email.as_str(), creds.passwd.as_str()))
-        //     .connect()
-        //     .await?
-        //     .send(message)
-        //     .await?
+    let (token, token_hash) = crate::auth::new_reset_token();
+    let expires_at = sqlx::types::chrono::Utc::now().timestamp() + RESET_TOKEN_TTL;
+    if let Err(x) =
+        crate::models::PasswordReset::insert(token_hash, user.username(), expires_at).await
+    {
+        tracing::error!("problem while storing the reset token {x:?}");
+        return Err(ServerFnError::new("Unexpected error, please try later"));
     }
-    return Ok(String::from(
-        "Email sent. Check email and click the reset url link inside.",
-    ));
+    // not the Host header, the client picks it and the link would lead wherever it says
+    let uri = format!("{}/reset_password?token={}", crate::public_url(), token);
+    let email = crate::mailer::compose(
+        "reset_password",
+        &user.locale(),
+        user.email(),
+        &[("username", user.username()), ("url", uri)],
+    )
+    .map_err(|x| {
+        tracing::error!("problem while writing the reset email {x}");
+        ServerFnError::new("Unexpected error, please try later")
+    })?;
+    // the mail is sent in the background, a mail server hiccup does not fail the request
+    crate::mailer::queue_until(email, expires_at)
+        .await
+        .map_err(|x| {
+            tracing::error!("problem while queueing the reset email {x:?}");
+            ServerFnError::new("Unexpected error, please try later")
+        })?;
+    Ok(RESET_EMAIL_SENT.to_string())
 }
 
 #[tracing::instrument]
@@ -87,32 +97,38 @@ pub async fn reset_password_2(
             "Passwords do not match, please retry!".to_string(),
         ));
     }
-    let Ok(claims) = crate::auth::decode_token(token.as_str()) else {
-        tracing::info!("Invalid token provided");
-        return Err(ServerFnError::new("Invalid token provided!".to_string()));
+    let token_hash = crate::auth::hash_reset_token(&token);
+    let username = match crate::models::PasswordReset::username(&token_hash).await {
+        Ok(Some(username)) => username,
+        Ok(None) => {
+            tracing::info!("Invalid token provided");
+            return Err(ServerFnError::new(RESET_INVALID_LINK.to_string()));
+        }
+        Err(error) => {
+            tracing::error!(?error, "error while checking the reset token");
+            return Err(ServerFnError::new(RESET_INVALID_LINK.to_string()));
+        }
     };
-    let email = claims.claims.sub;
-    let Ok(user) = crate::models::User::get_email(email.clone()).await else {
+    let Ok(user) = crate::models::User::get(username.clone()).await else {
         tracing::info!("User does not exist");
-        return Err(ServerFnError::new("User does not exist!".to_string()));
+        return Err(ServerFnError::new(RESET_INVALID_LINK.to_string()));
     };
-    match user.set_password(password) {
-        Ok(u) => {
-            if let Err(error) = u.update().await {
-                tracing::error!(email, ?error, "error while resetting the password");
-                return Err(ServerFnError::new(error.to_string()));
-            } else {
-                // A real password reset would have a list of issued tokens and invalidation over
-                // the used ones. As this would grow much bigger in complexity, I prefer to write
-                // down this security vulnerability and left it simple :)
-                // message = String::from("Password successfully reset, please, proceed to login");
-                return Ok("Password successfully changed, please, proceed to login".to_string());
-            }
-        }
-        Err(x) => {
-            return Err(ServerFnError::new(x));
-        }
+    let user = user.set_password(password).map_err(ServerFnError::new)?;
+    // consuming only now keeps the link usable after a password that failed validation
+    if !crate::models::PasswordReset::consume(&token_hash)
+        .await
+        .unwrap_or(false)
+    {
+        return Err(ServerFnError::new(RESET_INVALID_LINK.to_string()));
+    }
+    if let Err(error) = user.update().await {
+        tracing::error!(username, ?error, "error while resetting the password");
+        return Err(ServerFnError::new(error.to_string()));
+    }
+    if let Err(error) = crate::auth::revoke_credentials(&username).await {
+        tracing::error!(username, ?error, "error while revoking credentials");
     }
+    Ok("Password successfully changed, please, proceed to login".to_string())
 }
 
 #[component]
@@ -234,7 +250,7 @@ pub fn ResetPasswd(token: ReadSignal<String>) -> Element {
                     p {
                         class: format!(
                             "font-medium {}",
-                            if reset_status().starts_with("Email sent")
+                            if reset_status() == RESET_EMAIL_SENT
                                 || reset_status().starts_with("Password successfully changed")
                                 || reset_status().starts_with("Passwords matched!")
                             {
@@ -1,12 +1,15 @@
 use dioxus::prelude::*;
 
+/// `(total, page, amount)` of a search and the matches on that page.
+type SearchResultsPage = ((i64, i64, i64), Vec<crate::models::MatchedArticles>);
+
 #[tracing::instrument]
 #[post("/api/search_fetch_results")]
 pub async fn search_fetch_results(
     search: String,
     page: i64,
     amount: i64,
-) -> Result<((i64, i64, i64), Vec<crate::models::MatchedArticles>), ServerFnError> {
+) -> Result<SearchResultsPage, ServerFnError> {
     dioxus::logger::tracing::info!("Starting search op");
     if search.is_empty() {
         Err(ServerFnError::new("Empty search string, hence ignore"))
@@ -34,7 +37,7 @@ pub async fn search_fetch_results(
 
 use crate::{
     components::{AuthorUserIcon, ButtonFav, SearchViewPrevNextButton},
-    models::MatchedArticles,
+    models::{HighlightSegment, MatchedArticles},
     views::article::CommentSection,
     SearchMeta, SearchString, SearchWindow,
 };
@@ -129,15 +132,15 @@ fn SearchViewList(
         div { class: "mb-2 p-4 bg-white dark:bg-gray-800 rounded-lg shadow-md text-gray-800 dark:text-gray-200",
             p {
                 span { class: "font-bold", "Title: " }
-                span { dangerous_inner_html: article.title.clone() }
+                HighlightedText { segments: article.title.clone() }
             }
             p {
                 span { class: "font-bold", "Description: " }
-                span { dangerous_inner_html: article.description.clone() }
+                HighlightedText { segments: article.description.clone() }
             }
             p {
                 span { class: "font-bold", "Body: " }
-                span { dangerous_inner_html: article.body.clone() }
+                HighlightedText { segments: article.body.clone() }
             }
             div { class: "flex justify-between",
                 div {
@@ -163,7 +166,11 @@ fn SearchViewList(
                                 false => open_article_cnt.set(open_article_cnt() - 1),
                             }
                         },
-                        {format!("{}", if show_article() { "Hide" } else { "Show Full Article" })}
+                        if show_article() {
+                            "Hide"
+                        } else {
+                            "Show Full Article"
+                        }
                     }
                     div {
                         if open_article_cnt() > 1 && show_article() {
@@ -187,6 +194,21 @@ fn SearchViewList(
     }
 }
 
+#[component]
+fn HighlightedText(segments: Vec<HighlightSegment>) -> Element {
+    rsx! {
+        span {
+            for segment in segments {
+                if segment.matched {
+                    span { class: "bg-yellow-300 text-gray-700", {segment.text} }
+                } else {
+                    {segment.text}
+                }
+            }
+        }
+    }
+}
+
 #[component]
 fn SearchArticleView(slug: ReadSignal<String>) -> Element {
     let article_resource =
@@ -211,7 +233,10 @@ fn SearchArticleView(slug: ReadSignal<String>) -> Element {
                                 }
                             }
                             div { class: "mb-5",
-                                p { class: "text-gray-800 dark:text-gray-300", {article_detail.article.body.clone()} }
+                                div {
+                                    class: "markdown-body text-gray-800 dark:text-gray-300",
+                                    dangerous_inner_html: article_detail.article.body_html.clone().unwrap_or_default(),
+                                }
                             }
                         }
                         div { class: "mb-5 px-1 py-1",
@@ -10,6 +10,10 @@ use serde::{Deserialize, Serialize};
 pub enum SettingsUpdateError {
     PasswordsNotMatch,
     Successful,
+    /// Saved, the new email address is used once confirmed from the link sent to it.
+    ConfirmEmail(String),
+    /// Saved, except for the new email address, for the given reason.
+    EmailNotChanged(String),
     ValidationError(String),
 }
 
@@ -22,23 +26,51 @@ pub async fn settings_update(
     password: String,
     confirm_password: String,
 ) -> Result<SettingsUpdateError, ServerFnError> {
+    use crate::auth::EmailChangeError;
+
     let user = get_user(header).await?;
     let username = user.username();
-    let user = match update_user_validation(user, image, bio, email, password, &confirm_password) {
+    let password_changed = !password.is_empty();
+    let email_changed = email != user.email();
+    let user = match update_user_validation(user, image, bio, password, &confirm_password) {
         Ok(x) => x,
         Err(x) => return Ok(x),
     };
-    user.update()
-        .await
-        .map(|_| SettingsUpdateError::Successful)
-        .map_err(move |x| {
-            tracing::error!(
-                "Problem while updating user: {} with error {}",
-                username,
-                x.to_string()
-            );
-            ServerFnError::new("Problem while updating user")
-        })
+    user.update().await.map_err(|x| {
+        tracing::error!(
+            "Problem while updating user: {} with error {}",
+            username,
+            x.to_string()
+        );
+        ServerFnError::new("Problem while updating user")
+    })?;
+    if password_changed {
+        // the UI logs out right after, every other device has to log in again too
+        crate::auth::revoke_credentials(&username)
+            .await
+            .map_err(|x| {
+                tracing::error!("Problem while revoking credentials of {}: {}", username, x);
+                ServerFnError::new("Problem while updating user")
+            })?;
+    }
+    if !email_changed {
+        return Ok(SettingsUpdateError::Successful);
+    }
+    // a new address only replaces the current one once it is confirmed, the rest is saved
+    // whether it can be changed or not
+    match crate::auth::change_email(&user, &email).await {
+        Ok(()) => Ok(SettingsUpdateError::ConfirmEmail(email)),
+        Err(
+            x
+            @ (EmailChangeError::Invalid(_) | EmailChangeError::Taken | EmailChangeError::TooSoon),
+        ) => Ok(SettingsUpdateError::EmailNotChanged(x.to_string())),
+        Err(EmailChangeError::Failed(x)) => {
+            tracing::error!("Problem while changing the email of {username}: {x}");
+            Ok(SettingsUpdateError::EmailNotChanged(
+                "Problem while changing the email, try again later".to_string(),
+            ))
+        }
+    }
 }
 
 #[cfg(feature = "server")]
@@ -46,7 +78,6 @@ fn update_user_validation(
     mut user: crate::models::User,
     image: String,
     bio: String,
-    email: String,
     password: String,
     confirm_password: &str,
 ) -> Result<crate::models::User, SettingsUpdateError> {
@@ -59,9 +90,7 @@ fn update_user_validation(
             .map_err(SettingsUpdateError::ValidationError)?;
     }
 
-    user.set_email(email)
-        .map_err(SettingsUpdateError::ValidationError)?
-        .set_bio(bio)
+    user.set_bio(bio)
         .map_err(SettingsUpdateError::ValidationError)?
         .set_image(image)
         .map_err(SettingsUpdateError::ValidationError)
@@ -69,7 +98,7 @@ fn update_user_validation(
 
 #[cfg(feature = "server")]
 async fn get_user(header: TypedHeader<Cookie>) -> Result<crate::models::User, ServerFnError> {
-    let Some(username) = crate::auth::get_username_from_cookie(header) else {
+    let Some(username) = crate::auth::get_username_from_cookie(header).await else {
         return Err(ServerFnError::new(
             "You need to be authenticated".to_string(),
         ));
@@ -87,25 +116,257 @@ pub async fn settings_get() -> Result<crate::models::User, ServerFnError> {
     get_user(header).await
 }
 
+#[cfg(feature = "server")]
+async fn get_session(
+    header: TypedHeader<Cookie>,
+) -> Result<crate::auth::TokenClaims, ServerFnError> {
+    crate::auth::get_session_from_cookie(header)
+        .await
+        .ok_or_else(|| ServerFnError::new("You need to be authenticated".to_string()))
+}
+
+#[get("/api/settings_sessions", header: TypedHeader<Cookie>)]
+pub async fn settings_sessions() -> Result<Vec<crate::models::Session>, ServerFnError> {
+    let session = get_session(header).await?;
+    crate::models::Session::for_user(&session.sub, &session.jti)
+        .await
+        .map_err(|x| {
+            tracing::error!("problem while getting the sessions {x}");
+            ServerFnError::new("Problem while getting the sessions")
+        })
+}
+
+#[post("/api/settings_sign_out_session", header: TypedHeader<Cookie>)]
+pub async fn settings_sign_out_session(id: String) -> Result<(), ServerFnError> {
+    let session = get_session(header).await?;
+    crate::models::Session::delete(&id, &session.sub)
+        .await
+        .map(|_| ())
+        .map_err(|x| {
+            tracing::error!("problem while revoking session {id}: {x}");
+            ServerFnError::new("Problem while signing out the session")
+        })
+}
+
+#[post("/api/settings_sign_out_others", header: TypedHeader<Cookie>)]
+pub async fn settings_sign_out_others() -> Result<(), ServerFnError> {
+    let session = get_session(header).await?;
+    crate::models::Session::delete_all_except(&session.sub, &session.jti)
+        .await
+        .map(|_| ())
+        .map_err(|x| {
+            tracing::error!("problem while revoking sessions of {}: {x}", session.sub);
+            ServerFnError::new("Problem while signing out the other sessions")
+        })
+}
+
+#[tracing::instrument]
+#[post("/api/settings_email_digest", header: TypedHeader<Cookie>)]
+pub async fn settings_email_digest(email_digest: String) -> Result<(), ServerFnError> {
+    let user = get_user(header).await?;
+    let username = user.username();
+    user.set_email_digest(email_digest)
+        .map_err(ServerFnError::new)?
+        .update_email_digest()
+        .await
+        .map(|_| ())
+        .map_err(|x| {
+            tracing::error!("Problem while updating the email digest of {username}: {x}");
+            ServerFnError::new("Problem while saving the email preferences")
+        })
+}
+
+#[tracing::instrument]
+#[post("/api/settings_locale", header: TypedHeader<Cookie>)]
+pub async fn settings_locale(locale: String) -> Result<(), ServerFnError> {
+    let user = get_user(header).await?;
+    let username = user.username();
+    user.set_locale(locale)
+        .map_err(ServerFnError::new)?
+        .update_locale()
+        .await
+        .map(|_| ())
+        .map_err(|x| {
+            tracing::error!("Problem while updating the language of {username}: {x}");
+            ServerFnError::new("Problem while saving the email preferences")
+        })
+}
+
+/// Sends the confirmation link again, to the address waiting to replace the current one or
+/// else to the current one. Returns where it went.
+#[tracing::instrument]
+#[post("/api/settings_resend_verification", header: TypedHeader<Cookie>)]
+pub async fn settings_resend_verification() -> Result<String, ServerFnError> {
+    let user = get_user(header).await?;
+    let email = match user.pending_email() {
+        Some(x) => x,
+        None if !user.email_verified() => user.email(),
+        None => {
+            return Err(ServerFnError::new(
+                "Your email address is already confirmed",
+            ))
+        }
+    };
+    let throttled = crate::auth::email_verification_throttled(&user.username())
+        .await
+        .map_err(|x| {
+            tracing::error!(
+                "Problem while looking at the confirmations of {}: {x}",
+                user.username()
+            );
+            ServerFnError::new("Problem while sending the confirmation")
+        })?;
+    if throttled {
+        return Err(ServerFnError::new(
+            "A link was sent a moment ago, check your inbox or try again later",
+        ));
+    }
+    crate::auth::send_email_verification(&user, &email)
+        .await
+        .map_err(|x| {
+            tracing::error!(
+                "Problem while sending the confirmation of {}: {x}",
+                user.username()
+            );
+            ServerFnError::new("Problem while sending the confirmation")
+        })?;
+    Ok(email)
+}
+
+/// Whether the second factor is on and how many recovery codes are left.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct TwoFactorStatus {
+    pub enabled: bool,
+    pub recovery_codes_left: i64,
+}
+
+/// A secret waiting for its first code, as a QR code and as text for manual entry.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct TwoFactorEnrollment {
+    pub qr_svg: String,
+    pub secret: String,
+}
+
+#[get("/api/settings_two_factor", header: TypedHeader<Cookie>)]
+pub async fn settings_two_factor() -> Result<TwoFactorStatus, ServerFnError> {
+    let username = get_user(header).await?.username();
+    let status = async {
+        Ok::<_, sqlx::Error>(TwoFactorStatus {
+            enabled: crate::models::TwoFactor::secret(&username).await?.is_some(),
+            recovery_codes_left: crate::models::TwoFactor::unused_recovery_codes(&username).await?,
+        })
+    };
+    status.await.map_err(|x| {
+        tracing::error!("Problem while getting the two-factor status of {username}: {x}");
+        ServerFnError::new("Problem while getting the two-factor authentication")
+    })
+}
+
+/// Makes a new secret for the authenticator app, it is only used once a code from it is
+/// confirmed.
+#[post("/api/settings_two_factor_start", header: TypedHeader<Cookie>)]
+pub async fn settings_two_factor_start() -> Result<TwoFactorEnrollment, ServerFnError> {
+    let username = get_user(header).await?.username();
+    let unexpected = |x: String| {
+        tracing::error!("Problem while setting up the second factor of {username}: {x}");
+        ServerFnError::new("Problem while setting up the two-factor authentication")
+    };
+    let enabled = crate::models::TwoFactor::secret(&username)
+        .await
+        .map_err(|x| unexpected(x.to_string()))?;
+    if enabled.is_some() {
+        return Err(ServerFnError::new(
+            "Two-factor authentication is already on",
+        ));
+    }
+    let secret = crate::auth::new_totp_secret();
+    crate::models::TwoFactor::start_enrollment(&username, &secret)
+        .await
+        .map_err(|x| unexpected(x.to_string()))?;
+    let qr_svg =
+        crate::auth::qr_svg(&crate::auth::otpauth_url(&username, &secret)).map_err(unexpected)?;
+    Ok(TwoFactorEnrollment { qr_svg, secret })
+}
+
+/// Turns the second factor on with a first code from the app, returns the recovery codes.
+#[post("/api/settings_two_factor_confirm", header: TypedHeader<Cookie>)]
+pub async fn settings_two_factor_confirm(code: String) -> Result<Vec<String>, ServerFnError> {
+    let username = get_user(header).await?.username();
+    crate::auth::confirm_enrollment(&username, &code)
+        .await
+        .map_err(|x| {
+            tracing::error!("Problem while enabling the second factor of {username}: {x}");
+            ServerFnError::new("Problem while setting up the two-factor authentication")
+        })?
+        .ok_or_else(|| ServerFnError::new("The code is not valid, try the next one"))
+}
+
+#[cfg(feature = "server")]
+async fn check_second_factor(username: &str, code: &str) -> Result<(), ServerFnError> {
+    crate::auth::check_second_factor(username, code)
+        .await
+        .map_err(|x| match x {
+            crate::auth::SecondFactorError::Database(x) => {
+                tracing::error!("Problem while checking the second factor of {username}: {x}");
+                ServerFnError::new("Problem while checking the code")
+            }
+            x => ServerFnError::new(x.to_string()),
+        })
+}
+
+/// Turns the second factor off, the password and `code` prove it is the user and that they
+/// still have it.
+#[post("/api/settings_two_factor_disable", header: TypedHeader<Cookie>)]
+pub async fn settings_two_factor_disable(
+    password: String,
+    code: String,
+) -> Result<(), ServerFnError> {
+    let username = get_user(header).await?.username();
+    if !crate::auth::password_matches(&username, &password).await {
+        return Err(ServerFnError::new("The password is not valid"));
+    }
+    check_second_factor(&username, &code).await?;
+    crate::models::TwoFactor::disable(&username)
+        .await
+        .map_err(|x| {
+            tracing::error!("Problem while disabling the second factor of {username}: {x}");
+            ServerFnError::new("Problem while turning off the two-factor authentication")
+        })
+}
+
+/// Replaces the recovery codes, `code` proves the user still has the second factor.
+#[post("/api/settings_two_factor_recovery_codes", header: TypedHeader<Cookie>)]
+pub async fn settings_two_factor_recovery_codes(
+    code: String,
+) -> Result<Vec<String>, ServerFnError> {
+    let username = get_user(header).await?.username();
+    check_second_factor(&username, &code).await?;
+    crate::auth::regenerate_recovery_codes(&username)
+        .await
+        .map_err(|x| {
+            tracing::error!("Problem while replacing the recovery codes of {username}: {x}");
+            ServerFnError::new("Problem while making new recovery codes")
+        })
+}
+
 #[component]
 pub fn Settings() -> Element {
-    let mut user_settings = use_signal(|| crate::models::User::default());
+    let mut user_settings = use_signal(crate::models::User::default);
     let mut passwd_visible = use_signal(|| false);
-    let mut update_status = use_signal(|| String::new());
-    let mut image_url = use_signal(|| String::new());
+    let mut update_status = use_signal(String::new);
+    let mut image_url = use_signal(String::new);
     let mut no_image_url_yet = use_signal(|| true);
-    let mut bio = use_signal(|| String::new());
+    let mut bio = use_signal(String::new);
     let mut no_bio_yet = use_signal(|| true);
-    let mut email = use_signal(|| String::new());
+    let mut email = use_signal(String::new);
     let mut no_email_yet = use_signal(|| true);
-    let mut passwd = use_signal(|| String::new());
-    let mut confirm_passwd = use_signal(|| String::new());
+    let mut passwd = use_signal(String::new);
+    let mut confirm_passwd = use_signal(String::new);
     let mut is_passwd_change = use_signal(|| false);
 
     let mut settings_fut = use_resource(move || async move {
-        match settings_get().await {
-            Ok(user) => user_settings.set(user),
-            Err(_) => (),
+        if let Ok(user) = settings_get().await {
+            user_settings.set(user)
         }
     });
 
@@ -145,8 +406,21 @@ pub fn Settings() -> Element {
         .await;
 
         match update_result {
-            Ok(SettingsUpdateError::Successful) => {
-                update_status.set("Successful.".to_string());
+            Ok(
+                result @ (SettingsUpdateError::Successful
+                | SettingsUpdateError::ConfirmEmail(_)
+                | SettingsUpdateError::EmailNotChanged(_)),
+            ) => {
+                update_status.set(match result {
+                    SettingsUpdateError::ConfirmEmail(email) => {
+                        format!("Successful. Open the link sent to {email} to start using it.")
+                    }
+                    SettingsUpdateError::EmailNotChanged(err) => {
+                        format!("Saved, except for the email: {err}")
+                    }
+                    _ => "Successful.".to_string(),
+                });
+                no_email_yet.set(true);
                 if is_passwd_change() {
                     let mut logged_user = use_context::<Signal<LoggedInUser>>();
                     logged_user.set(LoggedInUser(None));
@@ -239,6 +513,10 @@ pub fn Settings() -> Element {
                             },
                             value: if no_email_yet() { user_settings().email() } else { email() },
                         }
+                        EmailConfirmation {
+                            verified: user_settings().email_verified(),
+                            pending: user_settings().pending_email(),
+                        }
                     }
                     div { class: "mb-5 relative",
                         input {
@@ -317,17 +595,12 @@ pub fn Settings() -> Element {
                                 _ => false,
                             },
                             onclick: on_update,
-                            value: {
-                                format!(
-                                    "{}",
-                                    if passwd() == confirm_passwd() && !passwd().is_empty()
-                                        && !confirm_passwd().is_empty()
-                                    {
-                                        "Reset Password & Logout"
-                                    } else {
-                                        "Update Settings"
-                                    },
-                                )
+                            value: if passwd() == confirm_passwd() && !passwd().is_empty()
+                                && !confirm_passwd().is_empty()
+                            {
+                                "Reset Password & Logout"
+                            } else {
+                                "Update Settings"
                             },
                         }
                         button {
@@ -342,18 +615,358 @@ pub fn Settings() -> Element {
                             ),
                             onclick: on_cancel,
                             {
-                                format!(
-                                    "{}",
-                                    if update_status().starts_with("Successful.") {
-                                        "Back to Home"
-                                    } else {
-                                        "Cancel"
-                                    },
-                                )
+                                if update_status().starts_with("Successful.") {
+                                    "Back to Home"
+                                } else {
+                                    "Cancel"
+                                }
+                            }
+                        }
+                    }
+                }
+                EmailPreferences {}
+                TwoFactorSettings {}
+                ActiveSessions {}
+            }
+        }
+    }
+}
+
+/// Whether the email address is confirmed, with a way to get the link again.
+#[component]
+fn EmailConfirmation(verified: bool, pending: Option<String>) -> Element {
+    let mut status = use_signal(String::new);
+    let notice = match &pending {
+        Some(email) => format!("{email} is waiting for you to confirm it."),
+        None if !verified => {
+            "Your email address is not confirmed yet, no mail goes to it until it is.".to_string()
+        }
+        None => return rsx! {},
+    };
+
+    rsx! {
+        p { class: "text-sm text-gray-500 dark:text-gray-400 mt-1",
+            {notice}
+            " "
+            button {
+                r#type: "button",
+                class: "text-blue-500 underline cursor-pointer",
+                onclick: move |_| async move {
+                    match settings_resend_verification().await {
+                        Ok(email) => status.set(format!("Sent a new link to {email}.")),
+                        Err(err) => status.set(err.to_string()),
+                    }
+                },
+                "Send the link again"
+            }
+            " "
+            {status()}
+        }
+    }
+}
+
+#[component]
+fn EmailPreferences() -> Element {
+    let mut status = use_signal(String::new);
+    let user = use_resource(move || async move { settings_get().await });
+    let (current, locale) = match &*user.read() {
+        Some(Ok(x)) => (x.email_digest(), x.locale()),
+        _ => (String::new(), String::new()),
+    };
+
+    rsx! {
+        div { class: "mb-5",
+            h6 { class: "font-medium text-neutral-800 dark:text-gray-200 mb-2", "Email digest" }
+            div { class: "flex items-center gap-2",
+                select {
+                    class: "rounded border px-2 py-1 bg-white dark:bg-gray-800 dark:border-gray-600",
+                    disabled: current.is_empty(),
+                    onchange: move |ev| async move {
+                        match settings_email_digest(ev.value()).await {
+                            Ok(()) => status.set("Saved.".to_string()),
+                            Err(err) => status.set(format!("Unexpected error: {err}")),
+                        }
+                    },
+                    for digest in crate::models::EMAIL_DIGESTS {
+                        option {
+                            key: "{digest}",
+                            value: digest,
+                            selected: current == digest,
+                            {
+                                match digest {
+                                    "daily" => "Daily",
+                                    "weekly" => "Weekly",
+                                    _ => "Off",
+                                }
                             }
                         }
                     }
                 }
+                span { class: "text-sm text-gray-500 dark:text-gray-400",
+                    "Unread notifications, sent to your email address"
+                }
+            }
+            h6 { class: "font-medium text-neutral-800 dark:text-gray-200 my-2", "Email language" }
+            select {
+                class: "rounded border px-2 py-1 bg-white dark:bg-gray-800 dark:border-gray-600",
+                disabled: locale.is_empty(),
+                onchange: move |ev| async move {
+                    match settings_locale(ev.value()).await {
+                        Ok(()) => status.set("Saved.".to_string()),
+                        Err(err) => status.set(format!("Unexpected error: {err}")),
+                    }
+                },
+                for (code, name) in crate::models::LOCALES {
+                    option {
+                        key: "{code}",
+                        value: code,
+                        selected: locale == code,
+                        {name}
+                    }
+                }
+            }
+            p { class: "text-sm font-medium text-gray-500 dark:text-gray-400", {status()} }
+        }
+    }
+}
+
+/// Enrollment of an authenticator app, and once it is on, recovery codes and turning it off.
+#[component]
+fn TwoFactorSettings() -> Element {
+    let mut status = use_resource(move || async move { settings_two_factor().await });
+    let mut enrollment = use_signal(|| None::<TwoFactorEnrollment>);
+    let mut recovery_codes = use_signal(Vec::<String>::new);
+    let mut code = use_signal(String::new);
+    let mut password = use_signal(String::new);
+    let mut message = use_signal(String::new);
+
+    let on_start = move |_| async move {
+        message.set(String::new());
+        match settings_two_factor_start().await {
+            Ok(x) => enrollment.set(Some(x)),
+            Err(err) => message.set(err.to_string()),
+        }
+    };
+    let on_confirm = move |_| async move {
+        match settings_two_factor_confirm(code()).await {
+            Ok(codes) => {
+                enrollment.set(None);
+                recovery_codes.set(codes);
+                message.set(String::new());
+                status.restart();
+            }
+            Err(err) => message.set(err.to_string()),
+        }
+        code.set(String::new());
+    };
+    let on_new_codes = move |_| async move {
+        match settings_two_factor_recovery_codes(code()).await {
+            Ok(codes) => {
+                recovery_codes.set(codes);
+                message.set(String::new());
+                status.restart();
+            }
+            Err(err) => message.set(err.to_string()),
+        }
+        code.set(String::new());
+    };
+    let on_disable = move |_| async move {
+        match settings_two_factor_disable(password(), code()).await {
+            Ok(()) => {
+                recovery_codes.set(vec![]);
+                message.set("Two-factor authentication is off.".to_string());
+                status.restart();
+            }
+            Err(err) => message.set(err.to_string()),
+        }
+        code.set(String::new());
+        password.set(String::new());
+    };
+
+    let code_input = rsx! {
+        input {
+            class: "rounded border px-2 py-1 w-40 bg-white dark:bg-gray-800 dark:border-gray-600",
+            r#type: "text",
+            autocomplete: "one-time-code",
+            placeholder: "code",
+            value: code(),
+            oninput: move |ev| code.set(ev.value()),
+        }
+    };
+
+    rsx! {
+        div { class: "mb-5",
+            h6 { class: "font-medium text-neutral-800 dark:text-gray-200 mb-2",
+                "Two-factor authentication"
+            }
+            if !recovery_codes().is_empty() {
+                div { class: "mb-2 p-2 rounded border border-yellow-400 bg-yellow-50 dark:bg-gray-700",
+                    p { class: "text-sm text-gray-700 dark:text-gray-200",
+                        "Keep these recovery codes somewhere safe. Each one lets you in once without your authenticator app, they are not shown again."
+                    }
+                    ul { class: "grid grid-cols-2 gap-1 font-mono text-sm mt-2",
+                        for recovery_code in recovery_codes() {
+                            li { key: "{recovery_code}", {recovery_code.clone()} }
+                        }
+                    }
+                }
+            }
+            match (&*status.read(), enrollment()) {
+                (Some(Ok(TwoFactorStatus { enabled: true, recovery_codes_left })), _) => rsx! {
+                    p { class: "text-sm text-gray-500 dark:text-gray-400 mb-2",
+                        {
+                            format!(
+                                "On, {recovery_codes_left} recovery codes left. Enter a code to change it, and your password to turn it off.",
+                            )
+                        }
+                    }
+                    div { class: "flex items-center gap-2",
+                        {code_input}
+                        input {
+                            class: "rounded border px-2 py-1 w-40 bg-white dark:bg-gray-800 dark:border-gray-600",
+                            r#type: "password",
+                            autocomplete: "current-password",
+                            placeholder: "password",
+                            value: password(),
+                            oninput: move |ev| password.set(ev.value()),
+                        }
+                        button {
+                            r#type: "button",
+                            class: "text-blue-500 hover:rounded hover:border hover:bg-blue-100 px-1",
+                            onclick: on_new_codes,
+                            "New recovery codes"
+                        }
+                        button {
+                            r#type: "button",
+                            class: "text-red-400 hover:rounded hover:border hover:bg-red-100 px-1",
+                            onclick: on_disable,
+                            "Turn off"
+                        }
+                    }
+                },
+                (Some(Ok(_)), Some(TwoFactorEnrollment { qr_svg, secret })) => rsx! {
+                    p { class: "text-sm text-gray-500 dark:text-gray-400",
+                        "Scan this code with your authenticator app, then enter the code it shows."
+                    }
+                    div { class: "inline-block bg-white my-2", dangerous_inner_html: qr_svg }
+                    p { class: "text-xs text-gray-500 dark:text-gray-400 mb-2",
+                        "Or enter this key by hand: "
+                        span { class: "font-mono break-all", {secret} }
+                    }
+                    div { class: "flex items-center gap-2",
+                        {code_input}
+                        button {
+                            r#type: "button",
+                            class: "bg-blue-700 hover:bg-blue-800 px-3 py-1 text-white rounded-lg",
+                            onclick: on_confirm,
+                            "Confirm"
+                        }
+                    }
+                },
+                (Some(Ok(_)), None) => rsx! {
+                    div { class: "flex items-center gap-2",
+                        button {
+                            r#type: "button",
+                            class: "text-blue-500 hover:rounded hover:border hover:bg-blue-100 px-1",
+                            onclick: on_start,
+                            i { class: "fas fa-shield-halved" }
+                            span { class: "px-1", "Set up an authenticator app" }
+                        }
+                        span { class: "text-sm text-gray-500 dark:text-gray-400",
+                            "A code from your phone is asked after the password"
+                        }
+                    }
+                },
+                (Some(Err(err)), _) => rsx! {
+                    p { class: "text-red-500", "Could not load the two-factor authentication: {err}" }
+                },
+                (None, _) => rsx! {
+                    p { class: "text-gray-500 dark:text-gray-400", "Loading..." }
+                },
+            }
+            p { class: "text-sm font-medium text-red-500", {message()} }
+        }
+    }
+}
+
+#[component]
+fn ActiveSessions() -> Element {
+    let mut sessions_status = use_signal(String::new);
+    let mut sessions = use_resource(move || async move { settings_sessions().await });
+
+    let on_sign_out_others = move |_| async move {
+        match settings_sign_out_others().await {
+            Ok(()) => sessions_status.set(String::new()),
+            Err(err) => sessions_status.set(format!("Unexpected error: {err}")),
+        }
+        sessions.restart();
+    };
+
+    rsx! {
+        div { class: "mb-5",
+            div { class: "flex justify-between items-center mb-2",
+                h6 { class: "font-medium text-neutral-800 dark:text-gray-200",
+                    "Active sessions"
+                }
+                button {
+                    r#type: "button",
+                    class: "text-red-400 hover:rounded hover:border hover:bg-red-100 px-1",
+                    onclick: on_sign_out_others,
+                    i { class: "fas fa-right-from-bracket" }
+                    span { class: "px-1", "Sign out everywhere else" }
+                }
+            }
+            p { class: "font-medium text-red-500", {sessions_status()} }
+            match &*sessions.read() {
+                Some(Ok(list)) => rsx! {
+                    ul {
+                        for session in list.iter().cloned() {
+                            li {
+                                key: "{session.id}",
+                                class: "flex justify-between items-center py-2 border-b dark:border-gray-600",
+                                div {
+                                    p { class: "text-sm font-medium text-gray-800 dark:text-gray-200",
+                                        {session.user_agent.clone().unwrap_or("Unknown device".to_string())}
+                                    }
+                                    p { class: "text-xs text-gray-500 dark:text-gray-400",
+                                        {
+                                            format!(
+                                                "{} · signed in {} · last seen {}",
+                                                session.ip.clone().unwrap_or("unknown address".to_string()),
+                                                session.created_at,
+                                                session.last_seen.clone().unwrap_or(session.created_at.clone()),
+                                            )
+                                        }
+                                    }
+                                }
+                                if session.current {
+                                    span { class: "text-xs text-green-500 px-1", "This device" }
+                                } else {
+                                    button {
+                                        r#type: "button",
+                                        class: "text-red-400 hover:rounded hover:border hover:bg-red-100 px-1 text-sm",
+                                        onclick: move |_| {
+                                            let id = session.id.clone();
+                                            async move {
+                                                if let Err(err) = settings_sign_out_session(id).await {
+                                                    sessions_status.set(format!("Unexpected error: {err}"));
+                                                }
+                                                sessions.restart();
+                                            }
+                                        },
+                                        "Sign out"
+                                    }
+                                }
+                            }
+                        }
+                    }
+                },
+                Some(Err(err)) => rsx! {
+                    p { class: "text-red-500", "Could not load sessions: {err}" }
+                },
+                None => rsx! {
+                    p { class: "text-gray-500 dark:text-gray-400", "Loading..." }
+                },
             }
         }
     }
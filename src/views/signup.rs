@@ -28,7 +28,9 @@ pub fn SignUp() -> Element {
 
             match res_signup {
                 Ok(SignupResponse::Success) => {
-                    signup_status.set("Signup Successful".to_string());
+                    signup_status.set(
+                        "Signup Successful, check your email to confirm your address".to_string(),
+                    );
                     create_button_string.set("Login to Account Created?".to_string());
                 }
                 Ok(SignupResponse::ValidationError(validation_error)) => {
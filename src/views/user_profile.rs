@@ -11,19 +11,28 @@ use dioxus::{document, prelude::*, router::root_router};
 pub async fn profile_articles(
     username: String,
     favourites: bool,
+    drafts: bool,
     page: i64,
     amount: i64,
 ) -> Result<Vec<crate::models::Article>, ServerFnError> {
     let page = i64::from(page);
     let amount = i64::from(amount);
 
-    crate::models::Article::for_user_profile_home(username, favourites, page, amount, header)
-        .await
-        .map_err(|x| {
-            let err = format!("Error while getting user_profile articles: {x:?}");
-            tracing::error!("{err}");
-            ServerFnError::new("Could not retrieve articles, try again later")
-        })
+    // drafts only ever come back for the logged user's own profile
+    crate::models::Article::for_user_profile_home(
+        username,
+        favourites,
+        drafts,
+        page,
+        amount,
+        crate::auth::get_username_from_cookie(header).await,
+    )
+    .await
+    .map_err(|x| {
+        let err = format!("Error while getting user_profile articles: {x:?}");
+        tracing::error!("{err}");
+        ServerFnError::new("Could not retrieve articles, try again later")
+    })
 }
 
 #[component]
@@ -62,6 +71,7 @@ pub fn ProfilePage(profile_user: ReadSignal<String>, route_path: ReadSignal<Stri
         profile_articles(
             profile_user(),
             pagination().get_favourites(),
+            pagination().get_drafts(),
             pagination().get_page(),
             pagination().get_amount(),
         )
@@ -77,6 +87,9 @@ pub fn ProfilePage(profile_user: ReadSignal<String>, route_path: ReadSignal<Stri
                             div { class: "flex text-gray-800 dark:text-gray-200",
                                 UserArticlesTab { user: profile_user() }
                                 FavouritedArticlesTab { user: profile_user() }
+                                if logged_user().0.is_some_and(|x| x.username == profile_user()) {
+                                    DraftsTab { user: profile_user() }
+                                }
                             }
                             ItemsPerPage { route_path: route_path() }
                         }
@@ -146,6 +159,7 @@ fn UserArticlesTab(user: ReadSignal<String>) -> Element {
             pagination()
                 .reset_page()
                 .set_favourites(false)
+                .set_drafts(false)
                 .set_amount(page_amount().0)
                 .to_string()
         ));
@@ -161,12 +175,22 @@ fn UserArticlesTab(user: ReadSignal<String>) -> Element {
     rsx! {
         div { class: "mb-5 px-2",
             document::Title { "Profile of {user()}" }
+            document::Link {
+                rel: "alternate",
+                r#type: "application/rss+xml",
+                title: "Articles by {user()}",
+                href: "/profile/{urlencoding::encode(&user())}/feed.xml",
+            }
             button {
                 onclick: on_click,
                 r#type: "button",
                 class: format!(
                     "font-bold {}",
-                    if !pagination().get_favourites() { "border-b-8" } else { "cursor-pointer" },
+                    if !pagination().get_favourites() && !pagination().get_drafts() {
+                        "border-b-8"
+                    } else {
+                        "cursor-pointer"
+                    },
                 ),
 
                 {user()}
@@ -188,6 +212,7 @@ fn FavouritedArticlesTab(user: ReadSignal<String>) -> Element {
             user(),
             pagination()
                 .set_favourites(true)
+                .set_drafts(false)
                 .reset_page()
                 .set_amount(page_amount().0)
                 .to_string()
@@ -208,7 +233,11 @@ fn FavouritedArticlesTab(user: ReadSignal<String>) -> Element {
                 r#type: "button",
                 class: format!(
                     "font-bold {}",
-                    if pagination().get_favourites() { "border-b-8" } else { "cursor-pointer" },
+                    if pagination().get_favourites() && !pagination().get_drafts() {
+                        "border-b-8"
+                    } else {
+                        "cursor-pointer"
+                    },
                 ),
                 "Favourited Articles"
             }
@@ -216,6 +245,46 @@ fn FavouritedArticlesTab(user: ReadSignal<String>) -> Element {
     }
 }
 
+#[component]
+fn DraftsTab(user: ReadSignal<String>) -> Element {
+    let mut pagination = use_context::<Signal<Pagination>>();
+    let page_amount = use_context::<Signal<crate::PageAmount>>();
+
+    let nav = navigator();
+    let on_click = move |_| {
+        nav.push(format!(
+            "/profile/{}{}",
+            user(),
+            pagination()
+                .set_favourites(false)
+                .set_drafts(true)
+                .reset_page()
+                .set_amount(page_amount().0)
+        ));
+        let router_context = root_router();
+        let mut route_string = String::new();
+        if let Some(context) = router_context {
+            route_string = context.full_route_string()
+        }
+        if pagination() != Pagination::from(route_string.clone()) {
+            pagination.set(Pagination::from(route_string.clone()));
+        }
+    };
+    rsx! {
+        div { class: "mb-5 px-2",
+            button {
+                onclick: on_click,
+                r#type: "button",
+                class: format!(
+                    "font-bold {}",
+                    if pagination().get_drafts() { "border-b-8" } else { "cursor-pointer" },
+                ),
+                "My Drafts"
+            }
+        }
+    }
+}
+
 #[component]
 fn UserInfo(user: ReadSignal<String>) -> Element {
     let user_resource = use_resource(move || async move { user_profile(user()).await });
@@ -290,7 +359,7 @@ pub async fn user_profile(username: String) -> Result<UserProfileModel, ServerFn
         })?;
     let mut following = None;
 
-    if let Some(logged_user) = crate::auth::get_username_from_cookie(header) {
+    if let Some(logged_user) = crate::auth::get_username_from_cookie(header).await {
         let count: i64 = sqlx::query_scalar!(
             "
             Select count(*) from Follows where follower=$2 and influencer=$1
@@ -0,0 +1,53 @@
+use dioxus::{document, prelude::*};
+
+#[tracing::instrument]
+#[post("/api/verify_email")]
+pub async fn verify_email(token: String) -> Result<String, ServerFnError> {
+    let token_hash = crate::auth::hash_reset_token(&token);
+    match crate::models::EmailVerification::confirm(&token_hash).await {
+        Ok(Some(username)) => {
+            tracing::info!(username, "email confirmed");
+            Ok("Your email address is confirmed, thank you!".to_string())
+        }
+        Ok(None) => Err(ServerFnError::new(
+            "This link is invalid or has expired, send a new one from your settings.",
+        )),
+        Err(x)
+            if x.to_string()
+                .contains("UNIQUE constraint failed: Users.email") =>
+        {
+            Err(ServerFnError::new(
+                "This email address is already used by another account.",
+            ))
+        }
+        Err(x) => {
+            tracing::error!("problem while confirming an email {x:?}");
+            Err(ServerFnError::new("Unexpected error, please try later"))
+        }
+    }
+}
+
+/// Where the links of the confirmation mails lead, the address is confirmed on arrival.
+#[component]
+pub fn VerifyEmail(token: ReadSignal<String>) -> Element {
+    let result = use_resource(move || async move { verify_email(token()).await });
+
+    rsx! {
+        div { class: "fixed inset-0 z-50 flex items-center justify-center bg-gray-900 bg-opacity-60",
+            document::Title { "Confirm Email" }
+            div { class: "block rounded-lg bg-white dark:bg-gray-800 w-2/5 p-4 z-70",
+                h5 { class: "mb-5 text-xl font-medium leading-tight text-neutral-800 dark:text-gray-200",
+                    "Confirm your email address."
+                }
+                p { class: "mb-5 text-neutral-800 dark:text-gray-200",
+                    match &*result.read() {
+                        Some(Ok(msg)) => msg.clone(),
+                        Some(Err(err)) => err.to_string(),
+                        None => "Confirming...".to_string(),
+                    }
+                }
+                a { class: "text-blue-500 underline", href: "/", "Back to Home" }
+            }
+        }
+    }
+}